@@ -0,0 +1,14 @@
+//! This little test binary mixes `print!` with writes to a synced dup of
+//! stdout. The `print!` output sits in std's buffer until the synced writes
+//! flush it, so the output comes out in order.
+
+#![deny(warnings)]
+
+use std::io::prelude::*;
+
+fn main() {
+    let mut stdout = os_pipe::dup_stdout_synced().unwrap();
+    print!("print! ");
+    stdout.write_all(b"synced ").unwrap();
+    println!("print!");
+}
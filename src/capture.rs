@@ -0,0 +1,303 @@
+use crate::PipeReader;
+use std::io;
+use std::process::{Command, ExitStatus, Output};
+
+/// Which of a child's output streams a chunk of
+/// [`CapturedOutput`](struct.CapturedOutput.html) came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    /// The child's stdout.
+    Stdout,
+    /// The child's stderr.
+    Stderr,
+}
+
+/// The result of [`capture`](fn.capture.html). This is like
+/// [`std::process::Output`], plus a record of the order the output arrived in.
+///
+/// [`std::process::Output`]: https://doc.rust-lang.org/std/process/struct.Output.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedOutput {
+    /// The child's exit status.
+    pub status: ExitStatus,
+    /// Everything the child wrote to stdout.
+    pub stdout: Vec<u8>,
+    /// Everything the child wrote to stderr.
+    pub stderr: Vec<u8>,
+    /// The order the output arrived in, as a list of `(stream, length)` runs.
+    /// Consecutive reads from the same stream are merged into one run, and the
+    /// lengths of each stream's runs add up to the length of its output.
+    ///
+    /// This is the order the parent read the output, which is only
+    /// approximately the order the child wrote it. Output that the child
+    /// buffered internally (like stdout in many C programs) shows up when it
+    /// was flushed, and writes that happen close together can be seen in
+    /// either order. On Windows, where the streams are read on separate
+    /// threads, the order is even less precise.
+    pub chunks: Vec<(OutputStream, usize)>,
+}
+
+impl CapturedOutput {
+    /// Stdout and stderr interleaved in the order given by
+    /// [`chunks`](#structfield.chunks), similar to what you'd see in a
+    /// terminal.
+    pub fn interleaved(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.stdout.len() + self.stderr.len());
+        let mut stdout_pos = 0;
+        let mut stderr_pos = 0;
+        for &(stream, len) in &self.chunks {
+            let (bytes, pos) = match stream {
+                OutputStream::Stdout => (&self.stdout, &mut stdout_pos),
+                OutputStream::Stderr => (&self.stderr, &mut stderr_pos),
+            };
+            output.extend_from_slice(&bytes[*pos..*pos + len]);
+            *pos += len;
+        }
+        output
+    }
+}
+
+// The output read so far, before we have an exit status.
+#[derive(Default)]
+struct Collected {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    chunks: Vec<(OutputStream, usize)>,
+}
+
+impl Collected {
+    fn push(&mut self, stream: OutputStream, bytes: &[u8]) {
+        match stream {
+            OutputStream::Stdout => self.stdout.extend_from_slice(bytes),
+            OutputStream::Stderr => self.stderr.extend_from_slice(bytes),
+        }
+        match self.chunks.last_mut() {
+            Some((last_stream, len)) if *last_stream == stream => *len += bytes.len(),
+            _ => self.chunks.push((stream, bytes.len())),
+        }
+    }
+}
+
+impl From<CapturedOutput> for Output {
+    fn from(captured: CapturedOutput) -> Output {
+        Output {
+            status: captured.status,
+            stdout: captured.stdout,
+            stderr: captured.stderr,
+        }
+    }
+}
+
+/// Run a command to completion, and capture its stdout and stderr.
+///
+/// This connects new pipes to the child's stdout and stderr, drops `command`
+/// so that the parent's copies of the pipe writers are closed, and then reads
+/// both pipes at the same time, so that a child that fills up one of them
+/// can't deadlock. (See the "Common deadlocks" section in the [crate
+/// docs](index.html).) Stdin is left as whatever `command` was configured
+/// with, except that [`Stdio::piped`] stdin is closed right away, like
+/// [`Command::output`] does. Unlike
+/// [`Command::output`], this also records the order the output arrived in; see
+/// [`CapturedOutput::interleaved`](struct.CapturedOutput.html#method.interleaved).
+///
+/// On Unix, both pipes are read on the calling thread, with
+/// [`MergedReader`](struct.MergedReader.html). On Windows, stderr is read on a
+/// background thread.
+///
+/// Note that if the child spawns grandchildren that inherit its stdout or
+/// stderr, this waits for them to exit or close those too.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # if cfg!(not(unix)) { return Ok(()); }
+/// use std::process::Command;
+///
+/// let mut command = Command::new("sh");
+/// command.args(["-c", "echo foo; echo bar >&2"]);
+/// let output = os_pipe::capture(command)?;
+/// assert!(output.status.success());
+/// assert_eq!(output.stdout, b"foo\n");
+/// assert_eq!(output.stderr, b"bar\n");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Stdio::piped`]: https://doc.rust-lang.org/std/process/struct.Stdio.html#method.piped
+/// [`Command::output`]: https://doc.rust-lang.org/std/process/struct.Command.html#method.output
+pub fn capture(mut command: Command) -> io::Result<CapturedOutput> {
+    let (stdout_reader, stdout_writer) = crate::pipe()?;
+    let (stderr_reader, stderr_writer) = crate::pipe()?;
+    command.stdout(stdout_writer);
+    command.stderr(stderr_writer);
+    let mut child = command.spawn()?;
+    // Like Child::wait_with_output, close piped stdin, so that a child reading it sees EOF.
+    drop(child.stdin.take());
+    // The Command is holding the parent's copies of the writers. We won't see EOF until they're
+    // closed.
+    drop(command);
+
+    let mut collected = Collected::default();
+    if let Err(e) = read_both(stdout_reader, stderr_reader, &mut collected) {
+        // The child might be blocked writing to a pipe that no one is reading anymore. Don't wait
+        // on it forever.
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+    }
+    Ok(CapturedOutput {
+        status: child.wait()?,
+        stdout: collected.stdout,
+        stderr: collected.stderr,
+        chunks: collected.chunks,
+    })
+}
+
+#[cfg(unix)]
+fn read_both(
+    stdout_reader: PipeReader,
+    stderr_reader: PipeReader,
+    collected: &mut Collected,
+) -> io::Result<()> {
+    let mut merged = crate::MergedReader::new([stdout_reader, stderr_reader]);
+    let mut buf = vec![0; 1 << 16];
+    while let Some((index, n)) = merged.read(&mut buf)? {
+        let stream = if index == 0 {
+            OutputStream::Stdout
+        } else {
+            OutputStream::Stderr
+        };
+        collected.push(stream, &buf[..n]);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn read_both(
+    stdout_reader: PipeReader,
+    stderr_reader: PipeReader,
+    collected: &mut Collected,
+) -> io::Result<()> {
+    use std::io::prelude::*;
+    use std::sync::Mutex;
+
+    // Both threads push into the same Collected as they read, which is what gives us an
+    // approximate order.
+    fn drain(
+        mut reader: PipeReader,
+        stream: OutputStream,
+        collected: &Mutex<&mut Collected>,
+    ) -> io::Result<()> {
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => collected.lock().unwrap().push(stream, &buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    let collected = Mutex::new(collected);
+    std::thread::scope(|scope| {
+        let stderr_thread = scope.spawn(|| drain(stderr_reader, OutputStream::Stderr, &collected));
+        let stdout_result = drain(stdout_reader, OutputStream::Stdout, &collected);
+        let stderr_result = stderr_thread.join().unwrap();
+        stdout_result.and(stderr_result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::prelude::*;
+    use std::process::Stdio;
+    use std::thread;
+
+    #[test]
+    fn test_capture_large_output() {
+        // cat_both writes its input to stdout and then to stderr. With a megabyte of input, that
+        // deadlocks unless both pipes are read at the same time.
+        let input = vec![b'x'; 1_000_000];
+        let (stdin_reader, mut stdin_writer) = crate::pipe().unwrap();
+        let input_clone = input.clone();
+        let writer_thread = thread::spawn(move || stdin_writer.write_all(&input_clone).unwrap());
+        let mut command = Command::new(crate::tests::path_to_exe("cat_both"));
+        command.stdin(stdin_reader);
+        let output = capture(command).unwrap();
+        writer_thread.join().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, [&b"stdout: "[..], &input].concat());
+        assert_eq!(output.stderr, [&b"stderr: "[..], &input].concat());
+        let (stdout_total, stderr_total) =
+            output
+                .chunks
+                .iter()
+                .fold((0, 0), |(out, err), &(stream, len)| match stream {
+                    OutputStream::Stdout => (out + len, err),
+                    OutputStream::Stderr => (out, err + len),
+                });
+        assert_eq!(stdout_total, output.stdout.len());
+        assert_eq!(stderr_total, output.stderr.len());
+        assert_eq!(
+            output.interleaved().len(),
+            output.stdout.len() + output.stderr.len()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_capture_interleaved() {
+        // cat_both's stdout ends with a newline, so it's flushed before anything is written to
+        // stderr. When both pipes are ready, MergedReader reads stdout first, so this order is
+        // deterministic. On Windows the streams are read on separate threads, and it isn't.
+        let (stdin_reader, mut stdin_writer) = crate::pipe().unwrap();
+        stdin_writer.write_all(b"foo\n").unwrap();
+        drop(stdin_writer);
+        let mut command = Command::new(crate::tests::path_to_exe("cat_both"));
+        command.stdin(stdin_reader);
+        let output = capture(command).unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            output.chunks,
+            [(OutputStream::Stdout, 12), (OutputStream::Stderr, 12)]
+        );
+        assert_eq!(output.interleaved(), b"stdout: foo\nstderr: foo\n");
+        let output = Output::from(output);
+        assert_eq!(output.stdout, b"stdout: foo\n");
+        assert_eq!(output.stderr, b"stderr: foo\n");
+    }
+
+    #[test]
+    fn test_capture_piped_stdin() {
+        // If the parent kept the child's stdin open, cat would never exit.
+        let mut command = Command::new(crate::tests::path_to_exe("cat"));
+        command.stdin(Stdio::piped());
+        let output = capture(command).unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_interleaved() {
+        #[cfg(unix)]
+        use std::os::unix::process::ExitStatusExt;
+        #[cfg(windows)]
+        use std::os::windows::process::ExitStatusExt;
+
+        let output = CapturedOutput {
+            status: ExitStatus::from_raw(0),
+            stdout: b"stdout: ".to_vec(),
+            stderr: b"stderr: ".to_vec(),
+            chunks: vec![
+                (OutputStream::Stderr, 3),
+                (OutputStream::Stdout, 8),
+                (OutputStream::Stderr, 5),
+            ],
+        };
+        assert_eq!(output.interleaved(), b"stdstdout: err: ");
+    }
+}
@@ -0,0 +1,184 @@
+use std::io;
+use std::os::unix::prelude::*;
+use std::process::Command;
+
+/// An extension trait for passing pipes (or any other file descriptors) to a
+/// child process at specific fd numbers, like `3>` in the shell.
+///
+/// Pipes from this crate are non-inheritable, so normally they can only reach
+/// a child through its stdin, stdout, or stderr. This is for programs that
+/// take extra file descriptors, like `gpg --status-fd=3`. Only the child's
+/// copies are made inheritable. The parent's stay close-on-exec, so other
+/// children spawned at the same time don't inherit them.
+///
+/// This trait is only available on Unix.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use os_pipe::CommandFdExt;
+/// use std::io::prelude::*;
+/// use std::process::Command;
+///
+/// let (mut reader, writer) = os_pipe::pipe()?;
+/// let mut command = Command::new("sh");
+/// command.args(["-c", "echo status >&3"]);
+/// command.fd_mappings([(3, writer)])?;
+/// let mut child = command.spawn()?;
+/// // Avoid a deadlock! The Command is holding a copy of the writer.
+/// drop(command);
+///
+/// let mut output = String::new();
+/// reader.read_to_string(&mut output)?;
+/// child.wait()?;
+/// assert_eq!(output, "status\n");
+/// # Ok(())
+/// # }
+/// ```
+pub trait CommandFdExt {
+    /// Give the child each `(child_fd, fd)` pair's `fd` at the fd number
+    /// `child_fd`, replacing whatever the child would otherwise have there,
+    /// including stdin, stdout, or stderr for fds 0 through 2. Call this once
+    /// with all of the mappings. Mappings from separate calls can clobber each
+    /// other.
+    ///
+    /// Like a pipe passed to `Command::stdout`, the `Command` keeps its copies
+    /// of the fds open until it's dropped, so drop it after spawning. Returns
+    /// an error of kind [`InvalidInput`] if any `child_fd` is negative.
+    ///
+    /// When `spawn` runs, the standard library opens an internal pipe, which
+    /// the child uses to report errors from `exec`. The `Command` holds the
+    /// fds you pass in, so their numbers are never used for that pipe. But a
+    /// `child_fd` that's free in the parent at that moment could be, and then
+    /// mapping onto it would send the error report to your fd, and `spawn`
+    /// would succeed even though `exec` failed. Using the numbers of the fds
+    /// you pass in, or numbers that are open in the parent anyway (like 0
+    /// through 2), avoids that.
+    ///
+    /// [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+    fn fd_mappings<I, F>(&mut self, mappings: I) -> io::Result<&mut Command>
+    where
+        I: IntoIterator<Item = (RawFd, F)>,
+        F: Into<OwnedFd>;
+}
+
+impl CommandFdExt for Command {
+    fn fd_mappings<I, F>(&mut self, mappings: I) -> io::Result<&mut Command>
+    where
+        I: IntoIterator<Item = (RawFd, F)>,
+        F: Into<OwnedFd>,
+    {
+        let mappings: Vec<(RawFd, OwnedFd)> = mappings
+            .into_iter()
+            .map(|(child_fd, fd)| (child_fd, fd.into()))
+            .collect();
+        if mappings.iter().any(|&(child_fd, _)| child_fd < 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "child fd numbers can't be negative",
+            ));
+        }
+        let max_child_fd = match mappings.iter().map(|&(child_fd, _)| child_fd).max() {
+            Some(max) => max,
+            None => return Ok(self),
+        };
+        // In the child, dup2 onto one target could close the source of another mapping. Moving
+        // every source above the highest target first means that can't happen. These copies are
+        // close-on-exec, and dup2 clears that flag on the targets only.
+        let mut sources = Vec::with_capacity(mappings.len());
+        for (child_fd, fd) in &mappings {
+            let source =
+                unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, max_child_fd + 1) };
+            if source == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            sources.push((*child_fd, unsafe { OwnedFd::from_raw_fd(source) }));
+        }
+        // Keep the original fds open until the Command is dropped, so that the pipe std opens in
+        // spawn() can't reuse their numbers.
+        let originals: Vec<OwnedFd> = mappings.into_iter().map(|(_, fd)| fd).collect();
+        // SAFETY: The closure only calls dup2, which is async-signal-safe, and it doesn't
+        // allocate.
+        unsafe {
+            self.pre_exec(move || {
+                let _ = &originals;
+                for (child_fd, source) in &sources {
+                    if libc::dup2(source.as_raw_fd(), *child_fd) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::prelude::*;
+
+    #[test]
+    fn test_fd_mappings() {
+        let (mut output_reader, output_writer) = crate::pipe().unwrap();
+        let (input_reader, mut input_writer) = crate::pipe().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "cat <&4 >&3"]);
+        // If either pipe end is already fd 3 or 4 in the parent, this also exercises the case where
+        // one mapping would clobber another.
+        command
+            .fd_mappings([
+                (3, OwnedFd::from(output_writer)),
+                (4, OwnedFd::from(input_reader)),
+            ])
+            .unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+        input_writer.write_all(b"foo").unwrap();
+        drop(input_writer);
+        let mut output = String::new();
+        output_reader.read_to_string(&mut output).unwrap();
+        assert!(child.wait().unwrap().success());
+        assert_eq!(output, "foo");
+    }
+
+    #[test]
+    fn test_fd_mappings_exec_fails() {
+        // Map a pile of fds onto their own numbers, where std's exec error pipe could land if they
+        // were closed before spawning. The error from exec should still reach spawn().
+        let path = std::env::temp_dir().join(format!(
+            "os_pipe_test_not_executable_{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"").unwrap();
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let mappings: Vec<(RawFd, OwnedFd)> = (0..10)
+            .map(|_| {
+                let fd = OwnedFd::from(writer.try_clone().unwrap());
+                (fd.as_raw_fd(), fd)
+            })
+            .collect();
+        drop(writer);
+        let mut command = Command::new(&path);
+        command.fd_mappings(mappings).unwrap();
+        let err = command.spawn().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        drop(command);
+        std::fs::remove_file(&path).unwrap();
+        // Nothing was written to the pipe.
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_fd_mappings_negative() {
+        let (_reader, writer) = crate::pipe().unwrap();
+        let err = Command::new("true")
+            .fd_mappings([(-1, writer)])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,284 @@
+//! Named pipes (FIFOs), which live in the filesystem, so unrelated processes
+//! can open them by path.
+//!
+//! The functions here open FIFOs as the same [`PipeReader`] and [`PipeWriter`]
+//! types that [`pipe`] returns. Like those, they're opened with `O_CLOEXEC`,
+//! so they aren't inherited by child processes. They also check that the path
+//! really is a FIFO, and return an error of kind [`InvalidInput`] if it isn't.
+//!
+//! Opening a FIFO normally blocks until the other end is opened too, which can
+//! hang forever if the peer never shows up. The `_nonblocking` variants don't
+//! wait: [`open_reader_nonblocking`] always succeeds right away, and
+//! [`open_writer_nonblocking`] fails with `ENXIO` if there's no reader yet.
+//!
+//! This module is only available on Unix.
+//!
+//! # Example
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use std::io::prelude::*;
+//!
+//! let path = std::env::temp_dir().join(format!("os_pipe_fifo_example_{}", std::process::id()));
+//! os_pipe::fifo::create(&path, 0o600)?;
+//! let writer_path = path.clone();
+//! let writer_thread = std::thread::spawn(move || -> std::io::Result<()> {
+//!     // This blocks until the reader below opens the FIFO.
+//!     let mut writer = os_pipe::fifo::open_writer(&writer_path)?;
+//!     writer.write_all(b"hello")
+//! });
+//! let mut reader = os_pipe::fifo::open_reader(&path)?;
+//! let mut output = String::new();
+//! reader.read_to_string(&mut output)?;
+//! writer_thread.join().unwrap()?;
+//! std::fs::remove_file(&path)?;
+//! assert_eq!(output, "hello");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`PipeReader`]: ../struct.PipeReader.html
+//! [`PipeWriter`]: ../struct.PipeWriter.html
+//! [`pipe`]: ../fn.pipe.html
+//! [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+//! [`open_reader_nonblocking`]: fn.open_reader_nonblocking.html
+//! [`open_writer_nonblocking`]: fn.open_writer_nonblocking.html
+
+use crate::{PipeReader, PipeWriter};
+use std::ffi::{CString, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::raw::c_char;
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Create a new FIFO at `path`, with the permission bits in `mode` (minus the
+/// process's umask), like `mkfifo(1)`. This fails with an error of kind
+/// [`AlreadyExists`] if `path` exists.
+///
+/// [`AlreadyExists`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.AlreadyExists
+pub fn create(path: impl AsRef<Path>, mode: u32) -> io::Result<()> {
+    let path = CString::new(path.as_ref().as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+    let ret = unsafe { libc::mkfifo(path.as_ptr(), mode as libc::mode_t) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn open(path: &Path, options: &mut OpenOptions, nonblocking: bool) -> io::Result<OwnedFd> {
+    if nonblocking {
+        options.custom_flags(libc::O_NONBLOCK);
+    }
+    // The standard library always opens files with O_CLOEXEC.
+    let file: File = options.open(path)?;
+    if !file.metadata()?.file_type().is_fifo() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path is not a FIFO",
+        ));
+    }
+    Ok(file.into())
+}
+
+/// Open the FIFO at `path` for reading. This blocks until a writer opens it.
+pub fn open_reader(path: impl AsRef<Path>) -> io::Result<PipeReader> {
+    open(path.as_ref(), OpenOptions::new().read(true), false).map(PipeReader::from)
+}
+
+/// Open the FIFO at `path` for writing. This blocks until a reader opens it.
+pub fn open_writer(path: impl AsRef<Path>) -> io::Result<PipeWriter> {
+    open(path.as_ref(), OpenOptions::new().write(true), false).map(PipeWriter::from)
+}
+
+/// Open the FIFO at `path` for reading, without waiting for a writer. The
+/// reader is left in non-blocking mode, so reads from an empty FIFO return an
+/// error of kind [`WouldBlock`]. Reads return EOF when no writer is
+/// connected, including before the first writer opens it. Use
+/// [`set_nonblocking`](../struct.PipeReader.html#method.set_nonblocking) to
+/// switch to blocking reads.
+///
+/// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+pub fn open_reader_nonblocking(path: impl AsRef<Path>) -> io::Result<PipeReader> {
+    open(path.as_ref(), OpenOptions::new().read(true), true).map(PipeReader::from)
+}
+
+/// Open the FIFO at `path` for writing, without waiting for a reader. If no
+/// reader has it open, this fails with `ENXIO` instead of blocking. The writer
+/// is left in non-blocking mode. Use
+/// [`set_nonblocking`](../struct.PipeWriter.html#method.set_nonblocking) to
+/// switch to blocking writes.
+pub fn open_writer_nonblocking(path: impl AsRef<Path>) -> io::Result<PipeWriter> {
+    open(path.as_ref(), OpenOptions::new().write(true), true).map(PipeWriter::from)
+}
+
+/// A FIFO in a new private temporary directory, which is deleted along with
+/// the directory when the `TempFifo` is dropped.
+///
+/// This is useful for emulating the shell's `<(...)` process substitution,
+/// for programs that insist on reading or writing a path rather than stdin or
+/// stdout. The directory is created with `mkdtemp` in
+/// [`std::env::temp_dir`], so it's only accessible by the current user, and
+/// the FIFO's name can't collide with anything else.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+/// use std::process::{Command, Stdio};
+///
+/// let fifo = os_pipe::fifo::TempFifo::new()?;
+/// let child = Command::new("cat")
+///     .arg(fifo.path())
+///     .stdout(Stdio::piped())
+///     .spawn()?;
+/// // This blocks until cat opens the FIFO.
+/// let mut writer = fifo.open_writer()?;
+/// writer.write_all(b"hello")?;
+/// drop(writer);
+/// let output = child.wait_with_output()?;
+/// assert_eq!(output.stdout, b"hello");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`std::env::temp_dir`]: https://doc.rust-lang.org/std/env/fn.temp_dir.html
+#[derive(Debug)]
+pub struct TempFifo {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl TempFifo {
+    /// Create a new temporary directory with a FIFO in it.
+    pub fn new() -> io::Result<TempFifo> {
+        let template = std::env::temp_dir().join("os_pipe.XXXXXX");
+        let template = CString::new(template.into_os_string().into_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "temp dir path contains a NUL byte",
+            )
+        })?;
+        let mut template = template.into_bytes_with_nul();
+        let ret = unsafe { libc::mkdtemp(template.as_mut_ptr() as *mut c_char) };
+        if ret.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        template.pop();
+        let dir = PathBuf::from(OsString::from_vec(template));
+        let path = dir.join("fifo");
+        if let Err(e) = create(&path, 0o600) {
+            let _ = fs::remove_dir(&dir);
+            return Err(e);
+        }
+        Ok(TempFifo { dir, path })
+    }
+
+    /// The path of the FIFO, to pass to another process.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open the FIFO for reading, with [`open_reader`](fn.open_reader.html).
+    /// This blocks until a writer opens it.
+    pub fn open_reader(&self) -> io::Result<PipeReader> {
+        open_reader(&self.path)
+    }
+
+    /// Open the FIFO for writing, with [`open_writer`](fn.open_writer.html).
+    /// This blocks until a reader opens it.
+    pub fn open_writer(&self) -> io::Result<PipeWriter> {
+        open_writer(&self.path)
+    }
+}
+
+impl Drop for TempFifo {
+    fn drop(&mut self) {
+        // Processes that already opened the FIFO keep working after it's unlinked.
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::prelude::*;
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A path in the temp dir that no other test is using. The caller removes it.
+    fn temp_path() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        std::env::temp_dir().join(format!(
+            "os_pipe_test_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    #[test]
+    fn test_fifo_nonblocking() {
+        let path = temp_path();
+        create(&path, 0o600).unwrap();
+        let err = create(&path, 0o600).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        // No reader yet.
+        let err = open_writer_nonblocking(&path).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENXIO));
+
+        let mut reader = open_reader_nonblocking(&path).unwrap();
+        // With a reader open, opening the writer doesn't block.
+        let mut writer = open_writer(&path).unwrap();
+        let err = reader.read(&mut [0; 10]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        writer.write_all(b"foo").unwrap();
+        drop(writer);
+        reader.set_nonblocking(false).unwrap();
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "foo");
+        assert!(!reader.is_inheritable().unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_fifo_not_a_fifo() {
+        let path = temp_path();
+        std::fs::write(&path, b"").unwrap();
+        let err = open_reader_nonblocking(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = open_writer(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_temp_fifo() {
+        let fifo = TempFifo::new().unwrap();
+        let path = fifo.path().to_owned();
+        let dir = path.parent().unwrap().to_owned();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // Hand the path to a child process, like <(...) in the shell.
+        let child = Command::new("cat")
+            .arg(&path)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut writer = fifo.open_writer().unwrap();
+        writer.write_all(b"foo").unwrap();
+        drop(writer);
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"foo");
+
+        drop(fifo);
+        assert!(!path.exists());
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,194 @@
+//! Async pipe types implementing the runtime-agnostic
+//! [`futures-io`](https://docs.rs/futures-io) traits, available on Unix with
+//! the `futures-io` Cargo feature.
+//!
+//! These wrap a [`PipeReader`] or [`PipeWriter`], put it in non-blocking mode,
+//! and register it with the global [`async-io`](https://docs.rs/async-io)
+//! reactor, which is what [`smol`](https://docs.rs/smol) and
+//! [`async-std`](https://docs.rs/async-std) use. They work with any executor.
+//! For Tokio, see the [`tokio`](../tokio/index.html) module instead, which has
+//! the same caveat about sharing non-blocking pipe ends.
+//!
+//! # Example
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use futures_lite::{AsyncReadExt, AsyncWriteExt};
+//! use os_pipe::futures_io::{AsyncPipeReader, AsyncPipeWriter};
+//!
+//! futures_lite::future::block_on(async {
+//!     let (reader, writer) = os_pipe::pipe()?;
+//!     let mut reader = AsyncPipeReader::new(reader)?;
+//!     let mut writer = AsyncPipeWriter::new(writer)?;
+//!     writer.write_all(b"hello").await?;
+//!     drop(writer);
+//!     let mut output = String::new();
+//!     reader.read_to_string(&mut output).await?;
+//!     assert_eq!(output, "hello");
+//!     Ok(())
+//! })
+//! # }
+//! ```
+//!
+//! [`PipeReader`]: ../struct.PipeReader.html
+//! [`PipeWriter`]: ../struct.PipeWriter.html
+
+use crate::{PipeReader, PipeWriter};
+use ::futures_io::{AsyncRead, AsyncWrite};
+use async_io::Async;
+use std::io;
+use std::os::unix::prelude::*;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The reading end of a pipe, registered with the `async-io` reactor.
+///
+/// This implements `futures_io::AsyncRead`.
+#[derive(Debug)]
+pub struct AsyncPipeReader(Async<PipeReader>);
+
+impl AsyncPipeReader {
+    /// Put `reader` in non-blocking mode and register it with the `async-io`
+    /// reactor.
+    pub fn new(reader: PipeReader) -> io::Result<AsyncPipeReader> {
+        Async::new(reader).map(AsyncPipeReader)
+    }
+
+    /// Get a reference to the underlying [`PipeReader`](../struct.PipeReader.html).
+    pub fn get_ref(&self) -> &PipeReader {
+        self.0.get_ref()
+    }
+
+    /// Deregister the reader from the reactor, put it back in blocking mode,
+    /// and return it.
+    pub fn into_inner(self) -> io::Result<PipeReader> {
+        let reader = self.0.into_inner()?;
+        reader.set_nonblocking(false)?;
+        Ok(reader)
+    }
+}
+
+impl TryFrom<PipeReader> for AsyncPipeReader {
+    type Error = io::Error;
+
+    fn try_from(reader: PipeReader) -> io::Result<AsyncPipeReader> {
+        AsyncPipeReader::new(reader)
+    }
+}
+
+impl AsyncRead for AsyncPipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        // Async implements AsyncRead for shared references when &T implements Read, which
+        // &PipeReader does. That lets us avoid async-io's unsafe IoSafe trait.
+        Pin::new(&mut &self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsFd for AsyncPipeReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for AsyncPipeReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The writing end of a pipe, registered with the `async-io` reactor.
+///
+/// This implements `futures_io::AsyncWrite`. Flushing does nothing, and
+/// closing doesn't close the pipe. Drop the writer to close it.
+#[derive(Debug)]
+pub struct AsyncPipeWriter(Async<PipeWriter>);
+
+impl AsyncPipeWriter {
+    /// Put `writer` in non-blocking mode and register it with the `async-io`
+    /// reactor.
+    pub fn new(writer: PipeWriter) -> io::Result<AsyncPipeWriter> {
+        Async::new(writer).map(AsyncPipeWriter)
+    }
+
+    /// Get a reference to the underlying [`PipeWriter`](../struct.PipeWriter.html).
+    pub fn get_ref(&self) -> &PipeWriter {
+        self.0.get_ref()
+    }
+
+    /// Deregister the writer from the reactor, put it back in blocking mode,
+    /// and return it.
+    pub fn into_inner(self) -> io::Result<PipeWriter> {
+        let writer = self.0.into_inner()?;
+        writer.set_nonblocking(false)?;
+        Ok(writer)
+    }
+}
+
+impl TryFrom<PipeWriter> for AsyncPipeWriter {
+    type Error = io::Error;
+
+    fn try_from(writer: PipeWriter) -> io::Result<AsyncPipeWriter> {
+        AsyncPipeWriter::new(writer)
+    }
+}
+
+impl AsyncWrite for AsyncPipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &self.0).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &self.0).poll_close(cx)
+    }
+}
+
+impl AsFd for AsyncPipeWriter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for AsyncPipeWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_async_pipe_a_megabyte_of_data() {
+        // As with the Tokio version of this test, we don't need a second thread. The executor
+        // interleaves the reads and writes when the pipe buffer fills up.
+        let data = vec![0xff; 1_000_000];
+        let (reader, writer) = crate::pipe().unwrap();
+        let mut reader = AsyncPipeReader::new(reader).unwrap();
+        let mut writer = AsyncPipeWriter::new(writer).unwrap();
+        let write_future = async {
+            writer.write_all(&data).await.unwrap();
+            drop(writer);
+        };
+        let read_future = async {
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).await.unwrap();
+            out
+        };
+        let ((), out) = future::block_on(future::zip(write_future, read_future));
+        assert_eq!(out, data);
+    }
+}
@@ -131,10 +131,100 @@ mod sys;
 #[path = "windows.rs"]
 mod sys;
 
+mod capture;
+#[cfg(unix)]
+mod command_ext;
+#[cfg(unix)]
+pub mod fifo;
+#[cfg(all(unix, feature = "futures-io"))]
+pub mod futures_io;
+#[cfg(unix)]
+mod merged_reader;
+#[cfg(all(unix, feature = "mio"))]
+mod mio;
+mod pipeline;
+#[cfg(unix)]
+mod redirect;
+// signal_pipe has to save and restore errno, which libc exposes differently on each platform.
+// These are the ones signal.rs knows about.
+#[cfg(any(
+    target_os = "aix",
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "emscripten",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "haiku",
+    target_os = "hurd",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "tvos",
+    target_os = "visionos",
+    target_os = "watchos",
+))]
+mod signal;
+#[cfg(unix)]
+mod splice;
+mod synced;
+mod tee_writer;
+#[cfg(all(unix, feature = "tokio"))]
+pub mod tokio;
+#[cfg(unix)]
+mod waker;
+
+pub use capture::{capture, CapturedOutput, OutputStream};
+#[cfg(unix)]
+pub use command_ext::CommandFdExt;
+#[cfg(unix)]
+pub use merged_reader::MergedReader;
+pub use pipeline::{Pipeline, PipelineHandle, PipelineStatus};
+#[cfg(unix)]
+pub use redirect::{redirect_stderr, redirect_stdin, redirect_stdout, CaptureGuard, RedirectGuard};
+#[cfg(any(
+    target_os = "aix",
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "emscripten",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "haiku",
+    target_os = "hurd",
+    target_os = "illumos",
+    target_os = "ios",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "redox",
+    target_os = "solaris",
+    target_os = "tvos",
+    target_os = "visionos",
+    target_os = "watchos",
+))]
+pub use signal::signal_pipe;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use splice::tee;
+#[cfg(unix)]
+pub use splice::{splice, SpliceFlags};
+pub use synced::{dup_stderr_synced, dup_stdout_synced, SyncedWriter};
+pub use tee_writer::{BrokenPipePolicy, TeeWriter};
+#[cfg(unix)]
+pub use waker::Waker;
+
 /// The reading end of a pipe, returned by [`pipe`](fn.pipe.html).
 ///
 /// `PipeReader` implements `Into<Stdio>`, so you can pass it as an argument to
 /// `Command::stdin` to spawn a child process that reads from the pipe.
+///
+/// On Unix with the `mio` Cargo feature, `PipeReader` also implements
+/// `mio::event::Source`. Put it in [non-blocking
+/// mode](#method.set_nonblocking) before registering it.
 #[derive(Debug)]
 pub struct PipeReader(
     // We use std::fs::File here for two reasons: OwnedFd and OwnedHandle are platform-specific,
@@ -146,18 +236,123 @@ impl PipeReader {
     pub fn try_clone(&self) -> io::Result<PipeReader> {
         self.0.try_clone().map(PipeReader)
     }
+
+    /// Move this reader into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, reads from an empty pipe return an error of kind
+    /// [`WouldBlock`] instead of waiting for a writer. Note that on Unix this
+    /// flag belongs to the underlying open file description, so it's shared with
+    /// every copy made by [`try_clone`](#method.try_clone), including copies
+    /// given to child processes.
+    ///
+    /// Windows doesn't support non-blocking anonymous pipes, and enabling
+    /// non-blocking mode there returns an error of kind [`Unsupported`].
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        sys::set_nonblocking(self, nonblocking)
+    }
+
+    /// Get the size of the pipe's buffer, in bytes. Both ends of a pipe share
+    /// the same buffer. This uses `F_GETPIPE_SZ` on Linux, and other
+    /// platforms return an error of kind [`Unsupported`].
+    ///
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    pub fn capacity(&self) -> io::Result<usize> {
+        sys::capacity(self)
+    }
+
+    /// Resize the pipe's buffer, and return the new size in bytes. The kernel
+    /// may round the size up, and unprivileged processes can't exceed
+    /// `/proc/sys/fs/pipe-max-size`. Shrinking the buffer below the amount of
+    /// data currently in the pipe fails with `EBUSY`. This uses
+    /// `F_SETPIPE_SZ` on Linux, and other platforms return an error of kind
+    /// [`Unsupported`]. To set the capacity of a new pipe, see
+    /// [`PipeBuilder::capacity`].
+    ///
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    /// [`PipeBuilder::capacity`]: struct.PipeBuilder.html#method.capacity
+    pub fn set_capacity(&self, capacity: usize) -> io::Result<usize> {
+        sys::set_capacity(self, capacity)
+    }
+
+    /// Check whether this reader is inheritable, meaning that child processes
+    /// spawned with `exec` or `CreateProcess` get a copy of it. This checks
+    /// `FD_CLOEXEC` on Unix and `HANDLE_FLAG_INHERIT` on Windows. Pipes from
+    /// [`pipe`](fn.pipe.html) aren't inheritable, unless they were built with
+    /// [`PipeBuilder::inheritable`](struct.PipeBuilder.html#method.inheritable).
+    pub fn is_inheritable(&self) -> io::Result<bool> {
+        sys::is_inheritable(self)
+    }
+
+    /// Make this reader inheritable or not. Unlike non-blocking mode, this flag
+    /// belongs to this handle alone, not to its clones.
+    ///
+    /// You don't need this to pass a pipe to `Command::stdin`, `stdout`, or
+    /// `stderr`, which make their own inheritable copies. Note that an
+    /// inheritable handle leaks into *every* child spawned while it's open,
+    /// including children spawned by other threads, and a child holding a copy
+    /// of a writer keeps the reader from seeing EOF.
+    pub fn set_inheritable(&self, inheritable: bool) -> io::Result<()> {
+        sys::set_inheritable(self, inheritable)
+    }
+
+    /// Duplicate this reader onto the file descriptor number `fd`, like `dup2` or
+    /// `dup3`. Whatever `fd` referred to before is closed, and afterwards
+    /// reading or writing `fd` goes to this pipe. The new copy is inheritable if
+    /// `inheritable` is true. This is only available on Unix.
+    ///
+    /// To replace the standard streams, see [`redirect_stdin`],
+    /// [`redirect_stdout`], and [`redirect_stderr`], which also restore them.
+    ///
+    /// # Safety
+    ///
+    /// Closing `fd` out from under an `OwnedFd`, a `File`, or any other owner
+    /// violates [I/O safety]. The caller has to make sure `fd` isn't owned by
+    /// anything else, or that its owner expects it to be replaced, as with the
+    /// standard streams.
+    ///
+    /// [`redirect_stdin`]: fn.redirect_stdin.html
+    /// [`redirect_stdout`]: fn.redirect_stdout.html
+    /// [`redirect_stderr`]: fn.redirect_stderr.html
+    /// [I/O safety]: https://doc.rust-lang.org/std/io/index.html#io-safety
+    #[cfg(unix)]
+    pub unsafe fn dup_to(&self, fd: std::os::unix::io::RawFd, inheritable: bool) -> io::Result<()> {
+        unsafe { sys::dup_to(self, fd, inheritable) }
+    }
+
+    /// Get the number of bytes that can currently be read from the pipe
+    /// without blocking. This uses `FIONREAD` on Unix and `PeekNamedPipe` on
+    /// Windows.
+    ///
+    /// A return value of zero means either that the pipe is empty and a read
+    /// would block, or that all the writers are closed and a read would return
+    /// EOF. Note that other readers (including clones of this one) might
+    /// consume the data before you read it.
+    pub fn bytes_available(&self) -> io::Result<usize> {
+        sys::bytes_available(self)
+    }
 }
 
 impl io::Read for PipeReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.0.read(buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        self.0.read_vectored(bufs)
+    }
 }
 
 impl io::Read for &PipeReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         (&self.0).read(buf)
     }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        (&self.0).read_vectored(bufs)
+    }
 }
 
 impl From<PipeReader> for Stdio {
@@ -171,6 +366,10 @@ impl From<PipeReader> for Stdio {
 /// `PipeWriter` implements `Into<Stdio>`, so you can pass it as an argument to
 /// `Command::stdout` or `Command::stderr` to spawn a child process that writes
 /// to the pipe.
+///
+/// On Unix with the `mio` Cargo feature, `PipeWriter` also implements
+/// `mio::event::Source`. Put it in [non-blocking
+/// mode](#method.set_nonblocking) before registering it.
 #[derive(Debug)]
 pub struct PipeWriter(File);
 
@@ -178,13 +377,144 @@ impl PipeWriter {
     pub fn try_clone(&self) -> io::Result<PipeWriter> {
         self.0.try_clone().map(PipeWriter)
     }
+
+    /// Move this writer into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, writes to a full pipe return an error of kind
+    /// [`WouldBlock`] instead of waiting for a reader, and writes that only
+    /// partly fit return a short count. Note that on Unix this flag belongs to
+    /// the underlying open file description, so it's shared with every copy made
+    /// by [`try_clone`](#method.try_clone), including copies given to child
+    /// processes.
+    ///
+    /// Windows doesn't support non-blocking anonymous pipes, and enabling
+    /// non-blocking mode there returns an error of kind [`Unsupported`].
+    ///
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        sys::set_nonblocking(self, nonblocking)
+    }
+
+    /// Get the size of the pipe's buffer, in bytes. Both ends of a pipe share
+    /// the same buffer. This uses `F_GETPIPE_SZ` on Linux, and other
+    /// platforms return an error of kind [`Unsupported`].
+    ///
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    pub fn capacity(&self) -> io::Result<usize> {
+        sys::capacity(self)
+    }
+
+    /// Resize the pipe's buffer, and return the new size in bytes. The kernel
+    /// may round the size up, and unprivileged processes can't exceed
+    /// `/proc/sys/fs/pipe-max-size`. Shrinking the buffer below the amount of
+    /// data currently in the pipe fails with `EBUSY`. This uses
+    /// `F_SETPIPE_SZ` on Linux, and other platforms return an error of kind
+    /// [`Unsupported`]. To set the capacity of a new pipe, see
+    /// [`PipeBuilder::capacity`].
+    ///
+    /// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+    /// [`PipeBuilder::capacity`]: struct.PipeBuilder.html#method.capacity
+    pub fn set_capacity(&self, capacity: usize) -> io::Result<usize> {
+        sys::set_capacity(self, capacity)
+    }
+
+    /// Check whether this writer is inheritable, meaning that child processes
+    /// spawned with `exec` or `CreateProcess` get a copy of it. This checks
+    /// `FD_CLOEXEC` on Unix and `HANDLE_FLAG_INHERIT` on Windows. Pipes from
+    /// [`pipe`](fn.pipe.html) aren't inheritable, unless they were built with
+    /// [`PipeBuilder::inheritable`](struct.PipeBuilder.html#method.inheritable).
+    pub fn is_inheritable(&self) -> io::Result<bool> {
+        sys::is_inheritable(self)
+    }
+
+    /// Make this writer inheritable or not. Unlike non-blocking mode, this flag
+    /// belongs to this handle alone, not to its clones.
+    ///
+    /// You don't need this to pass a pipe to `Command::stdin`, `stdout`, or
+    /// `stderr`, which make their own inheritable copies. Note that an
+    /// inheritable handle leaks into *every* child spawned while it's open,
+    /// including children spawned by other threads, and a child holding a copy
+    /// of a writer keeps the reader from seeing EOF.
+    pub fn set_inheritable(&self, inheritable: bool) -> io::Result<()> {
+        sys::set_inheritable(self, inheritable)
+    }
+
+    /// Duplicate this writer onto the file descriptor number `fd`, like `dup2` or
+    /// `dup3`. Whatever `fd` referred to before is closed, and afterwards
+    /// reading or writing `fd` goes to this pipe. The new copy is inheritable if
+    /// `inheritable` is true. This is only available on Unix.
+    ///
+    /// To replace the standard streams, see [`redirect_stdin`],
+    /// [`redirect_stdout`], and [`redirect_stderr`], which also restore them.
+    ///
+    /// # Safety
+    ///
+    /// Closing `fd` out from under an `OwnedFd`, a `File`, or any other owner
+    /// violates [I/O safety]. The caller has to make sure `fd` isn't owned by
+    /// anything else, or that its owner expects it to be replaced, as with the
+    /// standard streams.
+    ///
+    /// [`redirect_stdin`]: fn.redirect_stdin.html
+    /// [`redirect_stdout`]: fn.redirect_stdout.html
+    /// [`redirect_stderr`]: fn.redirect_stderr.html
+    /// [I/O safety]: https://doc.rust-lang.org/std/io/index.html#io-safety
+    #[cfg(unix)]
+    pub unsafe fn dup_to(&self, fd: std::os::unix::io::RawFd, inheritable: bool) -> io::Result<()> {
+        unsafe { sys::dup_to(self, fd, inheritable) }
+    }
+
+    /// Write all of `buf` to the pipe in a single `write` call, which POSIX
+    /// guarantees won't be interleaved with writes from other threads or
+    /// processes, as long as `buf` is at most [`PIPE_BUF`] bytes. If `buf` is
+    /// larger than that, this returns an error of kind [`InvalidInput`]
+    /// without writing anything.
+    ///
+    /// In blocking mode, this waits until there's room for all of `buf`. In
+    /// non-blocking mode, either all of `buf` is written, or nothing is written
+    /// and the error kind is [`WouldBlock`]. This is only available on Unix.
+    ///
+    /// [`PIPE_BUF`]: constant.PIPE_BUF.html
+    /// [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+    /// [`WouldBlock`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock
+    #[cfg(unix)]
+    pub fn write_atomic(&self, buf: &[u8]) -> io::Result<()> {
+        if buf.len() > PIPE_BUF {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "atomic pipe writes can't be larger than PIPE_BUF",
+            ));
+        }
+        loop {
+            match io::Write::write(&mut &self.0, buf) {
+                Ok(n) if n == buf.len() => return Ok(()),
+                // This shouldn't be possible for a real pipe, but we might've been constructed
+                // from some other kind of file with FromRawFd.
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "atomic pipe write was split",
+                    ))
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
+// Write::is_write_vectored is still unstable, so we can't forward it. Callers like BufWriter will
+// assume that vectored writes aren't efficient, but forwarding write_vectored still turns explicit
+// scatter/gather writes into a single writev on Unix.
 impl io::Write for PipeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.0.write(buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.0.write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.0.flush()
     }
@@ -195,6 +525,10 @@ impl io::Write for &PipeWriter {
         (&self.0).write(buf)
     }
 
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        (&self.0).write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         (&self.0).flush()
     }
@@ -206,6 +540,15 @@ impl From<PipeWriter> for Stdio {
     }
 }
 
+/// The largest write that's guaranteed to be atomic on a pipe, in bytes. See
+/// [`PipeWriter::write_atomic`](struct.PipeWriter.html#method.write_atomic).
+///
+/// This is 4096 on Linux. On other Unix platforms, we use the POSIX minimum of
+/// 512, which is also the actual value on macOS and the BSDs. This is only
+/// available on Unix.
+#[cfg(unix)]
+pub const PIPE_BUF: usize = sys::PIPE_BUF;
+
 /// Open a new pipe and return a [`PipeReader`] and [`PipeWriter`] pair.
 ///
 /// This corresponds to the `pipe2` library call on Posix and the
@@ -214,10 +557,127 @@ impl From<PipeWriter> for Stdio {
 /// processes won't receive a copy of them unless they're explicitly
 /// passed as stdin/stdout/stderr.
 ///
+/// To open a pipe with different settings, use [`PipeBuilder`].
+///
 /// [`PipeReader`]: struct.PipeReader.html
 /// [`PipeWriter`]: struct.PipeWriter.html
+/// [`PipeBuilder`]: struct.PipeBuilder.html
 pub fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
-    sys::pipe()
+    PipeBuilder::new().build()
+}
+
+/// Like [`pipe`](fn.pipe.html), but optionally open both ends in non-blocking
+/// mode.
+///
+/// This is a shortcut for [`PipeBuilder::nonblocking`]. On Linux and most
+/// other Unix platforms, this sets `O_NONBLOCK` atomically with `pipe2`. See
+/// [`PipeReader::set_nonblocking`] and [`PipeWriter::set_nonblocking`] for
+/// what non-blocking mode means. Windows doesn't support non-blocking
+/// anonymous pipes, and passing `true` there returns an error of kind
+/// [`Unsupported`].
+///
+/// [`PipeBuilder::nonblocking`]: struct.PipeBuilder.html#method.nonblocking
+/// [`PipeReader::set_nonblocking`]: struct.PipeReader.html#method.set_nonblocking
+/// [`PipeWriter::set_nonblocking`]: struct.PipeWriter.html#method.set_nonblocking
+/// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+pub fn pipe_with(nonblocking: bool) -> io::Result<(PipeReader, PipeWriter)> {
+    PipeBuilder::new().nonblocking(nonblocking).build()
+}
+
+/// Options for opening a pipe, when the defaults of [`pipe`](fn.pipe.html)
+/// aren't what you need.
+///
+/// This works like
+/// [`std::fs::OpenOptions`](https://doc.rust-lang.org/std/fs/struct.OpenOptions.html):
+/// configure a builder, and then call [`build`](#method.build) to open the
+/// pipe. Options that the current platform doesn't support cause `build` to
+/// return an error of kind [`Unsupported`], rather than being silently
+/// ignored.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// // A pipe whose reader can be polled in an event loop, but whose writer
+/// // can be handed to a child process that expects blocking writes.
+/// let (reader, writer) = os_pipe::PipeBuilder::new()
+///     .reader_nonblocking(true)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+#[derive(Clone, Debug, Default)]
+pub struct PipeBuilder {
+    inheritable: bool,
+    reader_nonblocking: bool,
+    writer_nonblocking: bool,
+    direct: bool,
+    capacity: Option<usize>,
+}
+
+impl PipeBuilder {
+    /// Create a builder with the same settings as [`pipe`](fn.pipe.html):
+    /// non-inheritable, blocking, and with the platform's default capacity.
+    pub fn new() -> PipeBuilder {
+        PipeBuilder::default()
+    }
+
+    /// Whether child processes should inherit both ends of the pipe. The
+    /// default is `false`, which sets `O_CLOEXEC` on Unix. Pipes passed
+    /// explicitly as a child's stdin/stdout/stderr reach the child either way.
+    pub fn inheritable(&mut self, inheritable: bool) -> &mut PipeBuilder {
+        self.inheritable = inheritable;
+        self
+    }
+
+    /// Open both ends in non-blocking mode. See
+    /// [`PipeReader::set_nonblocking`](struct.PipeReader.html#method.set_nonblocking).
+    /// This isn't supported on Windows.
+    pub fn nonblocking(&mut self, nonblocking: bool) -> &mut PipeBuilder {
+        self.reader_nonblocking = nonblocking;
+        self.writer_nonblocking = nonblocking;
+        self
+    }
+
+    /// Open only the reading end in non-blocking mode. This isn't supported
+    /// on Windows.
+    pub fn reader_nonblocking(&mut self, nonblocking: bool) -> &mut PipeBuilder {
+        self.reader_nonblocking = nonblocking;
+        self
+    }
+
+    /// Open only the writing end in non-blocking mode. This isn't supported
+    /// on Windows.
+    pub fn writer_nonblocking(&mut self, nonblocking: bool) -> &mut PipeBuilder {
+        self.writer_nonblocking = nonblocking;
+        self
+    }
+
+    /// Open the pipe in "packet mode", using `O_DIRECT`. Each write becomes a
+    /// separate packet, and each read returns at most one packet. Writes
+    /// larger than `PIPE_BUF` are split into multiple packets. This is only
+    /// supported on Linux. See
+    /// [`pipe(2)`](https://man7.org/linux/man-pages/man2/pipe.2.html).
+    pub fn direct(&mut self, direct: bool) -> &mut PipeBuilder {
+        self.direct = direct;
+        self
+    }
+
+    /// Request a buffer size for the pipe, in bytes. On Linux this uses
+    /// `F_SETPIPE_SZ`, and the kernel may round the size up. On Windows this
+    /// is passed to `CreatePipe`, which treats it as a suggestion. Other
+    /// platforms don't support this.
+    pub fn capacity(&mut self, capacity: usize) -> &mut PipeBuilder {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Open a new pipe with these settings.
+    pub fn build(&self) -> io::Result<(PipeReader, PipeWriter)> {
+        sys::pipe(self)
+    }
 }
 
 /// Get a duplicated copy of the current process's standard input, as a
@@ -242,7 +702,8 @@ pub fn dup_stdin() -> io::Result<PipeReader> {
 /// [`PipeWriter`](struct.PipeWriter.html).
 ///
 /// Writing directly to this pipe isn't recommended, because it's not
-/// synchronized with [`std::io::stdout`]. [`PipeWriter`] implements
+/// synchronized with [`std::io::stdout`]. (For that, see
+/// [`dup_stdout_synced`](fn.dup_stdout_synced.html).) [`PipeWriter`] implements
 /// [`Into<Stdio>`], so it can be passed directly to [`Command::stdout`] or
 /// [`Command::stderr`]. This can be useful if you want the child's stderr to go
 /// to the parent's stdout.
@@ -261,7 +722,8 @@ pub fn dup_stdout() -> io::Result<PipeWriter> {
 /// [`PipeWriter`](struct.PipeWriter.html).
 ///
 /// Writing directly to this pipe isn't recommended, because it's not
-/// synchronized with [`std::io::stderr`]. [`PipeWriter`] implements
+/// synchronized with [`std::io::stderr`]. (For that, see
+/// [`dup_stderr_synced`](fn.dup_stderr_synced.html).) [`PipeWriter`] implements
 /// [`Into<Stdio>`], so it can be passed directly to [`Command::stdout`] or
 /// [`Command::stderr`]. This can be useful if you want the child's stdout to go
 /// to the parent's stderr.
@@ -285,7 +747,7 @@ mod tests {
     use std::sync::Once;
     use std::thread;
 
-    fn path_to_exe(name: &str) -> PathBuf {
+    pub(crate) fn path_to_exe(name: &str) -> PathBuf {
         // This project defines some associated binaries for testing, and we shell out to them in
         // these tests. `cargo test` doesn't automatically build associated binaries, so this
         // function takes care of building them explicitly, with the right debug/release flavor.
@@ -341,6 +803,61 @@ mod tests {
         assert_eq!(out, "some stuff");
     }
 
+    #[test]
+    #[cfg(not(windows))] // Windows doesn't have vectored IO for pipes, so std writes one buffer.
+    fn test_pipe_vectored() {
+        use std::io::{IoSlice, IoSliceMut};
+
+        let (mut reader, mut writer) = crate::pipe().unwrap();
+        // A small write won't fill the pipe buffer, so it won't block this thread. Pipe writes
+        // this small are also atomic, so we should never see a short write here.
+        let n = writer
+            .write_vectored(&[IoSlice::new(b"some "), IoSlice::new(b"stuff")])
+            .unwrap();
+        assert_eq!(n, 10);
+        drop(writer);
+        let mut first = [0; 4];
+        let mut second = [0; 6];
+        let n = reader
+            .read_vectored(&mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)])
+            .unwrap();
+        assert_eq!(n, 10);
+        assert_eq!(&first, b"some");
+        assert_eq!(&second, b" stuff");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_write_atomic() {
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let too_big = vec![0; crate::PIPE_BUF + 1];
+        let err = writer.write_atomic(&too_big).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        // Several threads write records that are each filled with a single byte value. If any of
+        // these writes got split, the reader would see mixed records.
+        let mut joiners = Vec::new();
+        for i in 0..4 {
+            let writer = writer.try_clone().unwrap();
+            joiners.push(thread::spawn(move || {
+                let record = vec![i as u8; crate::PIPE_BUF];
+                for _ in 0..100 {
+                    writer.write_atomic(&record).unwrap();
+                }
+            }));
+        }
+        drop(writer);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        for joiner in joiners {
+            joiner.join().unwrap();
+        }
+        assert_eq!(out.len(), 4 * 100 * crate::PIPE_BUF);
+        for record in out.chunks(crate::PIPE_BUF) {
+            assert!(record.iter().all(|&b| b == record[0]));
+        }
+    }
+
     #[test]
     fn test_pipe_no_data() {
         let (mut reader, writer) = crate::pipe().unwrap();
@@ -485,6 +1002,150 @@ mod tests {
         assert_eq!(out, "some stuff");
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_nonblocking() {
+        let (mut reader, mut writer) = crate::pipe_with(true).unwrap();
+        let mut buf = [0; 10];
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+        // Fill the pipe until writes would block.
+        let chunk = [0xff; 4096];
+        let mut total = 0;
+        loop {
+            match writer.write(&chunk) {
+                Ok(n) => total += n,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+        assert!(total > 0);
+
+        // Switch the reader back to blocking and drain the pipe.
+        reader.set_nonblocking(false).unwrap();
+        drop(writer);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out.len(), total);
+    }
+
+    #[test]
+    fn test_set_nonblocking() {
+        let (mut reader, writer) = crate::pipe().unwrap();
+        if cfg!(windows) {
+            let err = reader.set_nonblocking(true).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+            reader.set_nonblocking(false).unwrap();
+            writer.set_nonblocking(false).unwrap();
+            return;
+        }
+        reader.set_nonblocking(true).unwrap();
+        let mut buf = [0; 10];
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        // A closed writer is EOF, not WouldBlock.
+        drop(writer);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_builder_inheritable() {
+        let (reader, writer) = crate::pipe().unwrap();
+        assert!(!reader.is_inheritable().unwrap());
+        assert!(!writer.is_inheritable().unwrap());
+
+        let (reader, writer) = crate::PipeBuilder::new().inheritable(true).build().unwrap();
+        assert!(reader.is_inheritable().unwrap());
+        assert!(writer.is_inheritable().unwrap());
+    }
+
+    #[test]
+    fn test_set_inheritable() {
+        let (reader, writer) = crate::pipe().unwrap();
+        let writer_clone = writer.try_clone().unwrap();
+        writer.set_inheritable(true).unwrap();
+        assert!(writer.is_inheritable().unwrap());
+        // The flag belongs to each handle, not to the pipe.
+        assert!(!writer_clone.is_inheritable().unwrap());
+        assert!(!reader.is_inheritable().unwrap());
+        writer.set_inheritable(false).unwrap();
+        assert!(!writer.is_inheritable().unwrap());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_builder_reader_nonblocking() {
+        let (mut reader, mut writer) = crate::PipeBuilder::new()
+            .reader_nonblocking(true)
+            .build()
+            .unwrap();
+        let mut buf = [0; 10];
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        // The writer is still blocking, so this 1 MB write needs another thread.
+        let joiner = thread::spawn(move || writer.write_all(&[0; 1_000_000]));
+        reader.set_nonblocking(false).unwrap();
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        joiner.join().unwrap().unwrap();
+        assert_eq!(out.len(), 1_000_000);
+    }
+
+    #[test]
+    fn test_builder_direct() {
+        let result = crate::PipeBuilder::new().direct(true).build();
+        if !cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+            return;
+        }
+        // In packet mode, each write comes back as a separate read.
+        let (mut reader, mut writer) = result.unwrap();
+        writer.write_all(b"foo").unwrap();
+        writer.write_all(b"bar").unwrap();
+        let mut buf = [0; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"foo");
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"bar");
+    }
+
+    #[test]
+    fn test_capacity() {
+        let (reader, writer) = crate::pipe().unwrap();
+        if !cfg!(any(target_os = "linux", target_os = "android")) {
+            let err = writer.capacity().unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+            let err = reader.set_capacity(1 << 20).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+            return;
+        }
+        // The default pipe-max-size is 1 MiB, so we shouldn't need privileges for this.
+        let new_capacity = writer.set_capacity(1 << 20).unwrap();
+        assert!(new_capacity >= 1 << 20);
+        assert_eq!(writer.capacity().unwrap(), new_capacity);
+        assert_eq!(reader.capacity().unwrap(), new_capacity);
+
+        let (_reader, writer) = crate::PipeBuilder::new().capacity(1 << 20).build().unwrap();
+        assert!(writer.capacity().unwrap() >= 1 << 20);
+    }
+
+    #[test]
+    fn test_bytes_available() {
+        let (mut reader, mut writer) = crate::pipe().unwrap();
+        assert_eq!(reader.bytes_available().unwrap(), 0);
+        writer.write_all(b"some stuff").unwrap();
+        assert_eq!(reader.bytes_available().unwrap(), 10);
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.bytes_available().unwrap(), 6);
+        drop(writer);
+        assert_eq!(reader.bytes_available().unwrap(), 6);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(reader.bytes_available().unwrap(), 0);
+    }
+
     #[test]
     fn test_debug() {
         let (reader, writer) = crate::pipe().unwrap();
@@ -0,0 +1,203 @@
+use crate::PipeReader;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::os::unix::prelude::*;
+
+/// Read from several pipes at once, on a single thread, as data arrives.
+///
+/// Reading a child's stdout to EOF before reading its stderr can deadlock: if
+/// the child fills up its stderr pipe first, it blocks, and it never closes
+/// stdout. A `MergedReader` avoids that by waiting on all of its pipes with
+/// `poll(2)` and reading from whichever are ready. Each chunk of data is
+/// tagged with the index of the pipe it came from, in the order the pipes were
+/// given to [`new`](#method.new). Pipes that reach EOF are dropped, and once
+/// they all have, reading returns `None`. When several pipes are ready at
+/// once, they take turns.
+///
+/// The pipes can be in blocking or non-blocking mode, but reading from clones
+/// of them on other threads could make this block. This type is only available
+/// on Unix.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::process::Command;
+///
+/// let (stdout_reader, stdout_writer) = os_pipe::pipe()?;
+/// let (stderr_reader, stderr_writer) = os_pipe::pipe()?;
+/// let mut command = Command::new("sh");
+/// command.args(["-c", "echo foo; echo bar >&2"]);
+/// command.stdout(stdout_writer);
+/// command.stderr(stderr_writer);
+/// let mut child = command.spawn()?;
+/// // Close the Command's copies of the writers, so that we see EOF.
+/// drop(command);
+///
+/// let mut stdout = Vec::new();
+/// let mut stderr = Vec::new();
+/// for chunk in os_pipe::MergedReader::new([stdout_reader, stderr_reader]) {
+///     let (index, bytes) = chunk?;
+///     if index == 0 {
+///         stdout.extend_from_slice(&bytes);
+///     } else {
+///         stderr.extend_from_slice(&bytes);
+///     }
+/// }
+/// child.wait()?;
+/// assert_eq!(stdout, b"foo\n");
+/// assert_eq!(stderr, b"bar\n");
+/// # Ok(())
+/// # }
+/// ```
+pub struct MergedReader {
+    // Readers that have reached EOF become None, so that the indexes of the others don't change.
+    readers: Vec<Option<PipeReader>>,
+    // The index to check first after the next poll, so that one busy pipe can't starve the others.
+    next: usize,
+    // The iterator reads into this and copies out each chunk. It's allocated on first use, since
+    // callers of read() bring their own buffer.
+    buf: Vec<u8>,
+}
+
+impl fmt::Debug for MergedReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergedReader")
+            .field("readers", &self.readers)
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MergedReader {
+    /// Create a `MergedReader` for `readers`. Chunks read from the first
+    /// reader are tagged with index 0, the second with index 1, and so on.
+    pub fn new(readers: impl IntoIterator<Item = PipeReader>) -> MergedReader {
+        MergedReader {
+            readers: readers.into_iter().map(Some).collect(),
+            next: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Wait until at least one pipe is readable, read from it into `buf`, and
+    /// return the pipe's index and the number of bytes read. This returns
+    /// `None` when every pipe has reached EOF. `buf` must not be empty.
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, usize)>> {
+        if buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "MergedReader needs a non-empty buffer",
+            ));
+        }
+        loop {
+            let mut indexes = Vec::new();
+            let mut pollfds = Vec::new();
+            for (index, reader) in self.readers.iter().enumerate() {
+                if let Some(reader) = reader {
+                    indexes.push(index);
+                    pollfds.push(libc::pollfd {
+                        fd: reader.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    });
+                }
+            }
+            if pollfds.is_empty() {
+                return Ok(None);
+            }
+            let ret =
+                unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+            if ret == -1 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            // Start with the first ready pipe at or after self.next, wrapping around.
+            let start = indexes.iter().position(|&i| i >= self.next).unwrap_or(0);
+            for offset in 0..pollfds.len() {
+                let position = (start + offset) % pollfds.len();
+                // POLLHUP and POLLERR mean a read won't block either, and it'll tell us what
+                // happened. For a pipe, POLLHUP means the writers are closed.
+                if pollfds[position].revents == 0 {
+                    continue;
+                }
+                let index = indexes[position];
+                let reader = self.readers[index].as_ref().expect("reader is open");
+                match (&*reader).read(buf) {
+                    Ok(0) => self.readers[index] = None,
+                    Ok(n) => {
+                        self.next = index + 1;
+                        return Ok(Some((index, n)));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Each item is a chunk of up to 64 KiB, tagged with the index of the pipe it
+/// came from.
+impl Iterator for MergedReader {
+    type Item = io::Result<(usize, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Take the buffer out of self, so that we can pass both to read().
+        let mut buf = mem::take(&mut self.buf);
+        if buf.is_empty() {
+            buf = vec![0; 1 << 16];
+        }
+        let item = match self.read(&mut buf) {
+            Ok(Some((index, n))) => Some(Ok((index, buf[..n].to_vec()))),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        };
+        self.buf = buf;
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_merged_reader_doesnt_deadlock() {
+        // The writer thread fills up the second pipe before it writes anything to the first, so
+        // reading the first pipe to EOF before the second would deadlock.
+        let (reader0, mut writer0) = crate::pipe().unwrap();
+        let (reader1, mut writer1) = crate::pipe().unwrap();
+        let joiner = thread::spawn(move || {
+            writer1.write_all(&[1; 1_000_000]).unwrap();
+            writer0.write_all(&[0; 1_000_000]).unwrap();
+            writer1.write_all(&[1; 10]).unwrap();
+        });
+        let mut outputs = [Vec::new(), Vec::new()];
+        for chunk in MergedReader::new([reader0, reader1]) {
+            let (index, bytes) = chunk.unwrap();
+            outputs[index].extend_from_slice(&bytes);
+        }
+        joiner.join().unwrap();
+        assert_eq!(outputs[0], vec![0; 1_000_000]);
+        assert_eq!(outputs[1], vec![1; 1_000_010]);
+    }
+
+    #[test]
+    fn test_merged_reader_empty() {
+        let mut merged = MergedReader::new([]);
+        assert!(merged.read(&mut [0; 10]).unwrap().is_none());
+        let (reader, writer) = crate::pipe().unwrap();
+        drop(writer);
+        let mut merged = MergedReader::new([reader]);
+        assert!(merged.read(&mut [0; 10]).unwrap().is_none());
+        assert!(merged.next().is_none());
+    }
+}
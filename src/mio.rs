@@ -0,0 +1,95 @@
+// Implementations of mio::event::Source, enabled by the `mio` Cargo feature. These register the
+// underlying fd directly, the same way mio's own SourceFd does, but because the pipe end owns its
+// fd, callers don't need to keep a separate SourceFd alive that could outlive it.
+
+use crate::{PipeReader, PipeWriter};
+use ::mio::event::Source;
+use ::mio::unix::SourceFd;
+use ::mio::{Interest, Registry, Token};
+use std::io;
+use std::os::unix::prelude::*;
+
+impl Source for PipeReader {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl Source for PipeWriter {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::mio::{Events, Interest, Poll, Token};
+    use std::io::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mio_readable() {
+        let (mut reader, mut writer) = crate::pipe_with(true).unwrap();
+        let mut poll = Poll::new().unwrap();
+        poll.registry()
+            .register(&mut reader, Token(0), Interest::READABLE)
+            .unwrap();
+        poll.registry()
+            .register(&mut writer, Token(1), Interest::WRITABLE)
+            .unwrap();
+
+        // An empty pipe is writable but not readable.
+        let mut events = Events::with_capacity(8);
+        poll.poll(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        let tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, [Token(1)]);
+
+        poll.registry().deregister(&mut writer).unwrap();
+        writer.write_all(b"hello").unwrap();
+        poll.poll(&mut events, Some(Duration::from_secs(10)))
+            .unwrap();
+        let tokens: Vec<Token> = events.iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, [Token(0)]);
+
+        let mut buf = [0; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{PipeReader, PipeWriter};
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+
+#[derive(Debug)]
+struct Stage {
+    command: Command,
+    stderr_to_stdout: bool,
+}
+
+/// A builder for a shell-style pipeline of child processes, like `a | b | c`.
+///
+/// Each stage's stdout is connected to the next stage's stdin with a
+/// [`pipe`](fn.pipe.html). The first stage's stdin is whatever its `Command`
+/// was configured with, and the last stage's stdout is inherited unless you
+/// set it with [`stdout`](#method.stdout). Any stdout that you configure on
+/// the other stages is replaced. When the pipeline is spawned, all the
+/// `Command` objects are dropped, so that the parent doesn't hold any pipe
+/// writers open by accident. (See the "Common deadlocks" section in the [crate
+/// docs](index.html).)
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # if cfg!(not(unix)) { return Ok(()); }
+/// use std::io::prelude::*;
+/// use std::process::Command;
+///
+/// let mut echo = Command::new("sh");
+/// echo.args(["-c", "echo foo; echo bar >&2"]);
+/// let (mut reader, writer) = os_pipe::pipe()?;
+/// let status = os_pipe::Pipeline::new()
+///     .pipe(echo)
+///     .stderr_to_stdout()
+///     .pipe(Command::new("sort"))
+///     .stdout(writer)
+///     .status()?;
+/// assert!(status.success());
+///
+/// let mut output = String::new();
+/// reader.read_to_string(&mut output)?;
+/// assert_eq!(output, "bar\nfoo\n");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    stdout: Option<PipeWriter>,
+    pipefail: bool,
+}
+
+impl Pipeline {
+    /// Create a new pipeline with no stages.
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Add `command` to the end of the pipeline.
+    pub fn pipe(mut self, command: Command) -> Pipeline {
+        self.stages.push(Stage {
+            command,
+            stderr_to_stdout: false,
+        });
+        self
+    }
+
+    /// Send the stderr of the most recently added stage to the same place as
+    /// its stdout, like `2>&1` in the shell. If that's the last stage and
+    /// there's no [`stdout`](#method.stdout) set, this is the parent's stdout.
+    ///
+    /// Note that this is the parent's stdout even if you configured a different
+    /// stdout on the last stage's `Command`, which isn't what the shell would
+    /// do. `Command` doesn't let us read that setting back. To redirect both
+    /// streams of the last stage, use [`stdout`](#method.stdout) instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no stages have been added yet.
+    pub fn stderr_to_stdout(mut self) -> Pipeline {
+        self.stages
+            .last_mut()
+            .expect("stderr_to_stdout() called on an empty Pipeline")
+            .stderr_to_stdout = true;
+        self
+    }
+
+    /// Set the stdout of the last stage. By default it's inherited from the
+    /// parent.
+    pub fn stdout(mut self, writer: PipeWriter) -> Pipeline {
+        self.stdout = Some(writer);
+        self
+    }
+
+    /// If `true`, a pipeline whose last stage succeeds still fails if any
+    /// earlier stage failed, like `set -o pipefail` in Bash. See
+    /// [`PipelineStatus::status`](struct.PipelineStatus.html#method.status).
+    /// The default is `false`.
+    pub fn pipefail(mut self, pipefail: bool) -> Pipeline {
+        self.pipefail = pipefail;
+        self
+    }
+
+    /// Start every stage of the pipeline running. If any stage fails to
+    /// spawn, the stages that already started are killed and waited on, and
+    /// the error is returned. Spawning an empty pipeline returns an error of
+    /// kind [`InvalidInput`].
+    ///
+    /// [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+    pub fn spawn(self) -> io::Result<PipelineHandle> {
+        if self.stages.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't spawn an empty Pipeline",
+            ));
+        }
+        let mut children = Vec::new();
+        if let Err(e) = spawn_stages(self.stages, self.stdout, &mut children) {
+            for child in &mut children {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            return Err(e);
+        }
+        Ok(PipelineHandle {
+            children,
+            pipefail: self.pipefail,
+        })
+    }
+
+    /// Spawn the pipeline and wait for every stage to exit.
+    pub fn status(self) -> io::Result<PipelineStatus> {
+        self.spawn()?.wait()
+    }
+}
+
+fn spawn_stages(
+    stages: Vec<Stage>,
+    mut last_stdout: Option<PipeWriter>,
+    children: &mut Vec<Child>,
+) -> io::Result<()> {
+    let len = stages.len();
+    let mut stdin: Option<PipeReader> = None;
+    for (i, stage) in stages.into_iter().enumerate() {
+        let mut command = stage.command;
+        if let Some(reader) = stdin.take() {
+            command.stdin(reader);
+        }
+        let stdout = if i + 1 < len {
+            let (reader, writer) = crate::pipe()?;
+            stdin = Some(reader);
+            Some(writer)
+        } else {
+            last_stdout.take()
+        };
+        if stage.stderr_to_stdout {
+            let stderr = match &stdout {
+                Some(writer) => writer.try_clone()?,
+                None => crate::dup_stdout()?,
+            };
+            command.stderr(stderr);
+        }
+        if let Some(writer) = stdout {
+            command.stdout(writer);
+        }
+        children.push(command.spawn()?);
+        // Dropping the Command here closes the parent's copies of this stage's pipe ends.
+    }
+    Ok(())
+}
+
+/// A running [`Pipeline`](struct.Pipeline.html), returned by
+/// [`Pipeline::spawn`](struct.Pipeline.html#method.spawn).
+#[derive(Debug)]
+pub struct PipelineHandle {
+    children: Vec<Child>,
+    pipefail: bool,
+}
+
+impl PipelineHandle {
+    /// The child processes, one per stage, in order.
+    pub fn children(&mut self) -> &mut [Child] {
+        &mut self.children
+    }
+
+    /// Wait for every stage to exit. If waiting on one of them fails, the
+    /// others are still waited on before the error is returned.
+    pub fn wait(mut self) -> io::Result<PipelineStatus> {
+        let mut statuses = Vec::new();
+        let mut error = None;
+        for child in &mut self.children {
+            match child.wait() {
+                Ok(status) => statuses.push(status),
+                Err(e) => {
+                    if error.is_none() {
+                        error = Some(e);
+                    }
+                }
+            }
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+        Ok(PipelineStatus {
+            statuses,
+            pipefail: self.pipefail,
+        })
+    }
+}
+
+/// The exit statuses of every stage of a finished
+/// [`Pipeline`](struct.Pipeline.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineStatus {
+    statuses: Vec<ExitStatus>,
+    pipefail: bool,
+}
+
+impl PipelineStatus {
+    /// The exit status of each stage, in order.
+    pub fn statuses(&self) -> &[ExitStatus] {
+        &self.statuses
+    }
+
+    /// The status of the pipeline as a whole. Like in the shell, this is the
+    /// status of the last stage. With
+    /// [`pipefail`](struct.Pipeline.html#method.pipefail), it's instead the
+    /// status of the last stage that failed, if any did.
+    pub fn status(&self) -> ExitStatus {
+        if self.pipefail {
+            if let Some(failed) = self.statuses.iter().rev().find(|s| !s.success()) {
+                return *failed;
+            }
+        }
+        *self
+            .statuses
+            .last()
+            .expect("pipelines have at least one stage")
+    }
+
+    /// Whether [`status`](#method.status) is successful.
+    pub fn success(&self) -> bool {
+        self.status().success()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::path_to_exe;
+    use std::io::prelude::*;
+    use std::process::Stdio;
+    use std::thread;
+
+    #[test]
+    fn test_pipeline_three_stages() {
+        let (stdin_reader, mut stdin_writer) = crate::pipe().unwrap();
+        let (mut stdout_reader, stdout_writer) = crate::pipe().unwrap();
+        let mut first = Command::new(path_to_exe("cat"));
+        first.stdin(stdin_reader);
+        let handle = Pipeline::new()
+            .pipe(first)
+            .pipe(Command::new(path_to_exe("cat_both")))
+            .stderr_to_stdout()
+            .pipe(Command::new(path_to_exe("cat")))
+            .stdout(stdout_writer)
+            .spawn()
+            .unwrap();
+        // A large input makes sure that every stage is reading and writing concurrently.
+        let input = vec![b'x'; 1_000_000];
+        let input_clone = input.clone();
+        let writer_thread = thread::spawn(move || stdin_writer.write_all(&input_clone).unwrap());
+        let mut output = Vec::new();
+        stdout_reader.read_to_end(&mut output).unwrap();
+        writer_thread.join().unwrap();
+        let status = handle.wait().unwrap();
+        assert_eq!(status.statuses().len(), 3);
+        assert!(status.success());
+        // cat_both writes its stdout and stderr in that order, but they can arrive in either
+        // order.
+        assert_eq!(output.len(), 2 * input.len() + b"stdout: stderr: ".len());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_pipefail() {
+        let pipeline = || {
+            let mut fail = Command::new("sh");
+            fail.args(["-c", "exit 3"]);
+            Pipeline::new().pipe(fail).pipe(Command::new("true"))
+        };
+        let status = pipeline().status().unwrap();
+        assert!(status.success());
+        assert_eq!(status.statuses()[0].code(), Some(3));
+        let status = pipeline().pipefail(true).status().unwrap();
+        assert!(!status.success());
+        assert_eq!(status.status().code(), Some(3));
+    }
+
+    #[test]
+    fn test_pipeline_errors() {
+        let err = Pipeline::new().spawn().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        // The first stage gets killed when the second fails to spawn. Give it a null stdin anyway,
+        // so that it can't outlive the test waiting on ours.
+        let mut cat = Command::new(path_to_exe("cat"));
+        cat.stdin(Stdio::null());
+        let err = Pipeline::new()
+            .pipe(cat)
+            .pipe(Command::new("this-command-does-not-exist"))
+            .spawn()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
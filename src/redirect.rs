@@ -0,0 +1,309 @@
+use crate::{PipeReader, PipeWriter};
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::prelude::*;
+use std::thread::{self, JoinHandle};
+
+/// Restores one of the process's standard streams when it's dropped. Returned
+/// by [`redirect_stdin`](fn.redirect_stdin.html),
+/// [`redirect_stdout`](fn.redirect_stdout.html), and
+/// [`redirect_stderr`](fn.redirect_stderr.html).
+///
+/// If you redirect the same stream more than once, drop the guards in the
+/// reverse order you created them, so that each one restores what the
+/// previous guard replaced.
+#[must_use = "the stream is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct RedirectGuard {
+    target: RawFd,
+    original: OwnedFd,
+}
+
+impl RedirectGuard {
+    fn new(target: RawFd, replacement: BorrowedFd<'_>) -> io::Result<RedirectGuard> {
+        // Anything the standard library has buffered was written before the redirect, so it
+        // belongs to the original stream.
+        flush_std(target);
+        // The saved copy is close-on-exec, so children don't inherit it.
+        let original = unsafe { BorrowedFd::borrow_raw(target) }.try_clone_to_owned()?;
+        // The standard streams are inheritable, and their replacements should be too.
+        unsafe { crate::sys::dup_to(replacement, target, true)? };
+        Ok(RedirectGuard { target, original })
+    }
+}
+
+impl Drop for RedirectGuard {
+    fn drop(&mut self) {
+        flush_std(self.target);
+        // There's nothing useful to do with an error in a destructor.
+        let _ = unsafe { crate::sys::dup_to(&self.original, self.target, true) };
+    }
+}
+
+fn flush_std(target: RawFd) {
+    // Errors here would've been reported to whoever wrote to the stream, and there's no one to
+    // report them to now.
+    match target {
+        libc::STDOUT_FILENO => {
+            let _ = io::stdout().flush();
+        }
+        libc::STDERR_FILENO => {
+            let _ = io::stderr().flush();
+        }
+        _ => {}
+    }
+}
+
+/// Point the process's own stdin (fd 0) at `reader`, until the returned guard
+/// is dropped.
+///
+/// This affects everything in the process that reads fd 0, including C
+/// libraries and child processes that inherit stdin. Note that
+/// [`std::io::stdin`](https://doc.rust-lang.org/std/io/fn.stdin.html) has its
+/// own buffer, and anything it read ahead from the original stdin will still be
+/// returned first. This is only available on Unix.
+pub fn redirect_stdin(reader: &PipeReader) -> io::Result<RedirectGuard> {
+    RedirectGuard::new(libc::STDIN_FILENO, reader.as_fd())
+}
+
+/// Point the process's own stdout (fd 1) at `writer`, until the returned guard
+/// is dropped.
+///
+/// This is the reverse of [`dup_stdout`](fn.dup_stdout.html). It's useful for
+/// capturing output from C libraries that print directly to fd 1, or from
+/// child processes that inherit stdout. Rust's own
+/// [`std::io::stdout`](https://doc.rust-lang.org/std/io/fn.stdout.html) is
+/// flushed before the redirect and again before stdout is restored, so
+/// `print!` output goes to the right place. This is only available on Unix.
+///
+/// Stdout is global to the process, so other threads' output is redirected
+/// too. Also note that the test harness captures `println!` output without
+/// going through fd 1.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let (mut reader, writer) = os_pipe::pipe()?;
+/// let guard = os_pipe::redirect_stdout(&writer)?;
+/// // Write directly to fd 1, like a C library would.
+/// unsafe { libc::write(1, b"hello".as_ptr() as *const _, 5) };
+/// drop(guard);
+/// drop(writer);
+///
+/// let mut output = String::new();
+/// reader.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// # Ok(())
+/// # }
+/// ```
+pub fn redirect_stdout(writer: &PipeWriter) -> io::Result<RedirectGuard> {
+    RedirectGuard::new(libc::STDOUT_FILENO, writer.as_fd())
+}
+
+/// Point the process's own stderr (fd 2) at `writer`, until the returned guard
+/// is dropped. See [`redirect_stdout`](fn.redirect_stdout.html). This is only
+/// available on Unix.
+pub fn redirect_stderr(writer: &PipeWriter) -> io::Result<RedirectGuard> {
+    RedirectGuard::new(libc::STDERR_FILENO, writer.as_fd())
+}
+
+/// Capture everything written to the process's stdout or stderr, until
+/// [`finish`](#method.finish) is called.
+///
+/// This points fd 1 or fd 2 at a new pipe, like
+/// [`redirect_stdout`](fn.redirect_stdout.html), and reads the pipe on a
+/// background thread, so that large outputs can't fill the pipe and block the
+/// writer. That includes output from C libraries and from child processes that
+/// inherit the stream. `finish` restores the original stream and returns the
+/// captured bytes. Dropping the guard without calling `finish` restores the
+/// stream and discards the output. This is only available on Unix.
+///
+/// Like any redirect, this affects the whole process. Note that child
+/// processes that inherited the stream keep the pipe open after it's restored,
+/// and `finish` blocks until they exit or close it. Dropping the guard doesn't
+/// wait for them. The reader thread is left running in the background, and it
+/// exits when they're done.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// let capture = os_pipe::CaptureGuard::stdout()?;
+/// std::process::Command::new("echo").arg("hello").status()?;
+/// let output = capture.finish()?;
+/// assert_eq!(output, b"hello\n");
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "the stream is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct CaptureGuard {
+    redirect: Option<RedirectGuard>,
+    reader_thread: Option<JoinHandle<io::Result<Vec<u8>>>>,
+}
+
+impl CaptureGuard {
+    /// Start capturing stdout.
+    pub fn stdout() -> io::Result<CaptureGuard> {
+        CaptureGuard::new(libc::STDOUT_FILENO)
+    }
+
+    /// Start capturing stderr.
+    pub fn stderr() -> io::Result<CaptureGuard> {
+        CaptureGuard::new(libc::STDERR_FILENO)
+    }
+
+    fn new(target: RawFd) -> io::Result<CaptureGuard> {
+        let (mut reader, writer) = crate::pipe()?;
+        let redirect = RedirectGuard::new(target, writer.as_fd())?;
+        // Now the redirected stream holds the only writer, and restoring the stream closes it.
+        drop(writer);
+        let reader_thread = thread::spawn(move || {
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output)?;
+            Ok(output)
+        });
+        Ok(CaptureGuard {
+            redirect: Some(redirect),
+            reader_thread: Some(reader_thread),
+        })
+    }
+
+    /// Restore the original stream, and return everything that was written
+    /// to it since the capture started.
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        // Restoring the stream closes the pipe writer, so the reader thread hits EOF.
+        drop(self.redirect.take());
+        let thread = self.reader_thread.take().expect("only taken here");
+        match thread.join() {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "capture reader thread panicked",
+            )),
+        }
+    }
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        drop(self.redirect.take());
+        // Don't join the reader thread. A child holding the pipe open could block it forever.
+        // Dropping the handle detaches it, and it exits on its own.
+        drop(self.reader_thread.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // The standard streams are global, and tests run in parallel. Every test that redirects one
+    // takes this lock. The harness itself only writes to stdout, so tests use stdin and stderr.
+    static STDIO_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_redirect_stderr() {
+        let _lock = STDIO_LOCK.lock().unwrap();
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let guard = redirect_stderr(&writer).unwrap();
+        let n = unsafe { libc::write(libc::STDERR_FILENO, b"foo".as_ptr() as *const _, 3) };
+        assert_eq!(n, 3);
+        drop(guard);
+        drop(writer);
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "foo");
+    }
+
+    #[test]
+    fn test_redirect_stdin() {
+        let _lock = STDIO_LOCK.lock().unwrap();
+        let (reader, mut writer) = crate::pipe().unwrap();
+        let guard = redirect_stdin(&reader).unwrap();
+        writer.write_all(b"bar").unwrap();
+        let mut buf = [0; 3];
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut _, 3) };
+        assert_eq!(n, 3);
+        assert_eq!(&buf, b"bar");
+        assert_eq!(inode(libc::STDIN_FILENO), inode(reader.as_raw_fd()));
+        drop(guard);
+        assert_ne!(inode(libc::STDIN_FILENO), inode(reader.as_raw_fd()));
+    }
+
+    #[test]
+    fn test_capture_guard() {
+        let _lock = STDIO_LOCK.lock().unwrap();
+        let capture = CaptureGuard::stderr().unwrap();
+        // More than a pipe buffer's worth, which would block without the reader thread.
+        let big = vec![b'x'; 1_000_000];
+        let mut stderr = crate::dup_stderr().unwrap();
+        stderr.write_all(&big).unwrap();
+        drop(stderr);
+        // A child process that inherits stderr is captured too.
+        let status = std::process::Command::new("sh")
+            .args(["-c", "echo child >&2"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = capture.finish().unwrap();
+        assert_eq!(output, [&big[..], b"child\n"].concat());
+
+        // Dropping the guard restores stderr and discards what was captured.
+        let capture = CaptureGuard::stderr().unwrap();
+        drop(capture);
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let guard = redirect_stderr(&writer).unwrap();
+        unsafe { libc::write(libc::STDERR_FILENO, b"after".as_ptr() as *const _, 5) };
+        drop(guard);
+        drop(writer);
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "after");
+    }
+
+    #[test]
+    fn test_capture_guard_drop_doesnt_wait() {
+        let _lock = STDIO_LOCK.lock().unwrap();
+        let capture = CaptureGuard::stderr().unwrap();
+        // This child inherits the pipe and holds it open until we kill it.
+        let mut child = std::process::Command::new("sleep")
+            .arg("1000")
+            .spawn()
+            .unwrap();
+        drop(capture);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    fn inode(fd: RawFd) -> (libc::dev_t, libc::ino_t) {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::fstat(fd, &mut stat) }, 0);
+        (stat.st_dev, stat.st_ino)
+    }
+
+    #[test]
+    fn test_dup_to() {
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let (other_reader, other_writer) = crate::pipe().unwrap();
+        drop(other_reader);
+        // Point other_writer's fd at our pipe instead. The old pipe is closed.
+        unsafe { writer.dup_to(other_writer.as_raw_fd(), false).unwrap() };
+        assert!(!other_writer.is_inheritable().unwrap());
+        drop(writer);
+        (&other_writer).write_all(b"baz").unwrap();
+        drop(other_writer);
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "baz");
+
+        // Duplicating onto itself just sets the flag.
+        let (reader, _writer) = crate::pipe().unwrap();
+        unsafe { reader.dup_to(reader.as_raw_fd(), true).unwrap() };
+        assert!(reader.is_inheritable().unwrap());
+    }
+}
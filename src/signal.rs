@@ -0,0 +1,234 @@
+use crate::PipeReader;
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::prelude::*;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+// At least the highest signal number on each platform. Linux on MIPS has 127 signals, FreeBSD's
+// real-time signals go up to 126, and we allow the same on DragonFly. Other Linux targets stop at
+// 64, and the rest have fewer. Numbers in range that the platform doesn't have fail in sigaction.
+#[cfg(any(
+    all(
+        any(target_os = "linux", target_os = "android"),
+        any(
+            target_arch = "mips",
+            target_arch = "mips32r6",
+            target_arch = "mips64",
+            target_arch = "mips64r6"
+        )
+    ),
+    target_os = "freebsd",
+    target_os = "dragonfly",
+))]
+const MAX_SIGNAL: usize = 127;
+#[cfg(not(any(
+    all(
+        any(target_os = "linux", target_os = "android"),
+        any(
+            target_arch = "mips",
+            target_arch = "mips32r6",
+            target_arch = "mips64",
+            target_arch = "mips64r6"
+        )
+    ),
+    target_os = "freebsd",
+    target_os = "dragonfly",
+)))]
+const MAX_SIGNAL: usize = 64;
+
+// The write end of the pipe for each signal number, or -1. Signal handlers read these, so they're
+// atomics, and the fds they point to are never closed.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_FD: AtomicI32 = AtomicI32::new(-1);
+static SIGNAL_FDS: [AtomicI32; MAX_SIGNAL + 1] = [NO_FD; MAX_SIGNAL + 1];
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "redox",
+    target_os = "fuchsia",
+    target_os = "emscripten",
+    target_os = "hurd",
+))]
+unsafe fn errno_location() -> *mut c_int {
+    libc::__errno_location()
+}
+
+#[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn errno_location() -> *mut c_int {
+    libc::__errno()
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+    target_os = "freebsd",
+))]
+unsafe fn errno_location() -> *mut c_int {
+    libc::__error()
+}
+
+#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+unsafe fn errno_location() -> *mut c_int {
+    libc::___errno()
+}
+
+#[cfg(target_os = "haiku")]
+unsafe fn errno_location() -> *mut c_int {
+    libc::_errnop()
+}
+
+#[cfg(target_os = "aix")]
+unsafe fn errno_location() -> *mut c_int {
+    libc::_Errno()
+}
+
+extern "C" fn handler(signal: c_int) {
+    // This runs in between arbitrary instructions of the interrupted thread, so it can only make
+    // async-signal-safe calls, and it can't leave errno changed behind it.
+    unsafe {
+        let errno = *errno_location();
+        let fd = match SIGNAL_FDS.get(signal as usize) {
+            Some(fd) => fd.load(Ordering::Relaxed),
+            None => -1,
+        };
+        if fd != -1 {
+            let byte = signal as u8;
+            // The writer is non-blocking. If the pipe is full, this fails with EAGAIN and the
+            // signal is dropped, which is the best we can do without blocking in a handler.
+            libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+        }
+        *errno_location() = errno;
+    }
+}
+
+/// Install signal handlers for `signals`, and return a pipe that receives a
+/// byte with the signal's number every time one of them is delivered.
+///
+/// This is the "self-pipe trick". A signal handler can't safely do much more
+/// than write to a file descriptor, and this turns signals into ordinary
+/// readable events that a poll loop or a dedicated thread can handle. The
+/// handler is async-signal-safe: it doesn't allocate or take locks, it
+/// preserves `errno`, and it writes to a non-blocking pipe, so a full pipe
+/// drops the signal instead of blocking. Handlers are installed with
+/// `SA_RESTART`. The returned reader is in blocking mode, and like every pipe
+/// from this crate, it's non-inheritable.
+///
+/// The handlers replace any previous handlers for these signals, including
+/// ones from an earlier call to `signal_pipe`, and they're never uninstalled.
+/// Each call leaks the write end of its pipe, even if installing a handler
+/// fails, since a handler might still be using it. This returns an error of
+/// kind [`InvalidInput`] for signal numbers that are out of range, and
+/// `sigaction` fails for signals that can't be caught, like `SIGKILL`. If any
+/// handler fails to install, the ones installed by this call are removed
+/// again.
+///
+/// This is only available on Unix platforms where libc exposes `errno`: Linux,
+/// Android, the Apple platforms, the BSDs, Solaris, illumos, AIX, Haiku,
+/// Fuchsia, Redox, Emscripten, and Hurd.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let mut signals = os_pipe::signal_pipe(&[libc::SIGUSR1])?;
+/// unsafe { libc::raise(libc::SIGUSR1) };
+/// let mut signal = [0];
+/// signals.read_exact(&mut signal)?;
+/// assert_eq!(signal[0] as libc::c_int, libc::SIGUSR1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+pub fn signal_pipe(signals: &[c_int]) -> io::Result<PipeReader> {
+    for &signal in signals {
+        if signal <= 0 || signal as usize > MAX_SIGNAL {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "signal number out of range",
+            ));
+        }
+    }
+    let (reader, writer) = crate::PipeBuilder::new().writer_nonblocking(true).build()?;
+
+    // Point each signal at the new pipe before installing its handler, and remember what was
+    // there before, in case we need to undo it.
+    let mut installed = Vec::new();
+    let mut result = Ok(());
+    for &signal in signals {
+        let old_fd = SIGNAL_FDS[signal as usize].swap(writer.as_raw_fd(), Ordering::Relaxed);
+        let mut old_action: libc::sigaction = unsafe { mem::zeroed() };
+        let ret = unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handler as extern "C" fn(c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, &mut old_action)
+        };
+        if ret == -1 {
+            result = Err(io::Error::last_os_error());
+            SIGNAL_FDS[signal as usize].store(old_fd, Ordering::Relaxed);
+            break;
+        }
+        installed.push((signal, old_fd, old_action));
+    }
+    if let Err(e) = result {
+        for (signal, old_fd, old_action) in installed.into_iter().rev() {
+            unsafe { libc::sigaction(signal, &old_action, ptr::null_mut()) };
+            SIGNAL_FDS[signal as usize].store(old_fd, Ordering::Relaxed);
+        }
+        // A handler that ran before we put things back might have loaded the writer's fd and
+        // still be about to write to it, so it can't be closed here either.
+        let _ = writer.into_raw_fd();
+        return Err(e);
+    }
+
+    // Handlers can run at any point from here on, so the writer can never be closed.
+    let _ = writer.into_raw_fd();
+    Ok(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::prelude::*;
+
+    #[test]
+    fn test_signal_pipe() {
+        let mut reader = signal_pipe(&[libc::SIGUSR2, libc::SIGWINCH]).unwrap();
+        unsafe {
+            libc::raise(libc::SIGWINCH);
+            libc::raise(libc::SIGUSR2);
+        }
+        let mut signals = [0; 2];
+        reader.read_exact(&mut signals).unwrap();
+        assert_eq!(
+            signals,
+            [libc::SIGWINCH as u8, libc::SIGUSR2 as u8],
+            "raise() delivers the signal before it returns"
+        );
+    }
+
+    #[test]
+    fn test_signal_pipe_errors() {
+        let err = signal_pipe(&[0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = signal_pipe(&[MAX_SIGNAL as c_int + 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        // SIGKILL can't be caught. The SIGURG handler installed before it gets removed again.
+        let err = signal_pipe(&[libc::SIGURG, libc::SIGKILL]).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+        assert_eq!(
+            SIGNAL_FDS[libc::SIGURG as usize].load(Ordering::Relaxed),
+            -1
+        );
+    }
+}
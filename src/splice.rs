@@ -0,0 +1,401 @@
+use crate::{PipeReader, PipeWriter};
+use std::io;
+use std::ops::{BitOr, BitOrAssign};
+use std::os::raw::c_uint;
+use std::os::unix::prelude::*;
+
+/// Flags for [`splice`](fn.splice.html), corresponding to the `SPLICE_F_*`
+/// constants on Linux. Combine them with `|`.
+///
+/// Other platforms ignore these flags, except that [`splice`](fn.splice.html)
+/// doesn't support `NONBLOCK` there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SpliceFlags(c_uint);
+
+impl SpliceFlags {
+    /// No flags.
+    pub const NONE: SpliceFlags = SpliceFlags(0);
+    /// `SPLICE_F_MOVE`: ask the kernel to move pages instead of copying. This
+    /// is only a hint, and current kernels ignore it.
+    pub const MOVE: SpliceFlags = SpliceFlags(1);
+    /// `SPLICE_F_NONBLOCK`: don't block on the pipe, and return an error of
+    /// kind `WouldBlock` instead. The other file descriptor might still block,
+    /// unless it's also in non-blocking mode.
+    pub const NONBLOCK: SpliceFlags = SpliceFlags(2);
+    /// `SPLICE_F_MORE`: more data is coming in a later splice. This is a hint
+    /// for sockets, similar to `TCP_CORK`.
+    pub const MORE: SpliceFlags = SpliceFlags(4);
+
+    /// Whether all the flags in `other` are set in `self`.
+    pub fn contains(self, other: SpliceFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for SpliceFlags {
+    type Output = SpliceFlags;
+
+    fn bitor(self, other: SpliceFlags) -> SpliceFlags {
+        SpliceFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for SpliceFlags {
+    fn bitor_assign(&mut self, other: SpliceFlags) {
+        self.0 |= other.0;
+    }
+}
+
+/// Move up to `len` bytes from `from` to `to` without copying them through
+/// userspace, and return the number of bytes moved. Zero means `from` has
+/// reached EOF.
+///
+/// On Linux this is [`splice(2)`](https://man7.org/linux/man-pages/man2/splice.2.html),
+/// and at least one of `from` and `to` must be a pipe. Files and sockets use
+/// (and advance) their current offset. On other Unix platforms, this falls
+/// back to a single `read` of at most 64 KiB into a buffer, followed by
+/// writing all of that buffer. The bytes are gone from `from` once they're
+/// read, so if writing them fails, for example because `to` is a closed pipe,
+/// they're lost. For the same reason, the fallback can't stop partway with
+/// `WouldBlock`, and it returns an error of kind [`Unsupported`] if `flags`
+/// includes `NONBLOCK` or if `to` is in non-blocking mode. Like
+/// [`Read::read`](https://doc.rust-lang.org/std/io/trait.Read.html#tymethod.read),
+/// this might move fewer than `len` bytes even if more are available, so
+/// call it in a loop to move everything.
+///
+/// This is only available on Unix.
+///
+/// [`Unsupported`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Unsupported
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let (reader1, mut writer1) = os_pipe::pipe()?;
+/// let (mut reader2, writer2) = os_pipe::pipe()?;
+/// writer1.write_all(b"hello")?;
+/// let n = os_pipe::splice(&reader1, &writer2, 1 << 16, os_pipe::SpliceFlags::NONE)?;
+/// assert_eq!(n, 5);
+/// drop(writer2);
+/// let mut output = String::new();
+/// reader2.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// # Ok(())
+/// # }
+/// ```
+pub fn splice(from: impl AsFd, to: impl AsFd, len: usize, flags: SpliceFlags) -> io::Result<usize> {
+    sys_splice(from.as_fd(), to.as_fd(), len, flags)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sys_splice(
+    from: BorrowedFd,
+    to: BorrowedFd,
+    len: usize,
+    flags: SpliceFlags,
+) -> io::Result<usize> {
+    let res = unsafe {
+        libc::splice(
+            from.as_raw_fd(),
+            std::ptr::null_mut(),
+            to.as_raw_fd(),
+            std::ptr::null_mut(),
+            len,
+            flags.0,
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn sys_splice(
+    from: BorrowedFd,
+    to: BorrowedFd,
+    len: usize,
+    flags: SpliceFlags,
+) -> io::Result<usize> {
+    use std::fs::File;
+    use std::io::prelude::*;
+    use std::mem::ManuallyDrop;
+
+    // A WouldBlock from the write below would lose the bytes we already read.
+    let to_flags = unsafe { libc::fcntl(to.as_raw_fd(), libc::F_GETFL) };
+    if to_flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if flags.contains(SpliceFlags::NONBLOCK) || to_flags & libc::O_NONBLOCK != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "non-blocking splice is only supported on Linux",
+        ));
+    }
+    // Borrow the fds as Files without taking ownership of them. ManuallyDrop keeps us from closing
+    // them.
+    let mut from = ManuallyDrop::new(unsafe { File::from_raw_fd(from.as_raw_fd()) });
+    let mut to = ManuallyDrop::new(unsafe { File::from_raw_fd(to.as_raw_fd()) });
+    let mut buf = [0; 1 << 16];
+    let n = from.read(&mut buf[..len.min(1 << 16)])?;
+    to.write_all(&buf[..n])?;
+    Ok(n)
+}
+
+/// Copy up to `len` bytes from one pipe to another, without consuming them
+/// from `reader` and without copying them through userspace, and return the
+/// number of bytes copied.
+///
+/// This is [`tee(2)`](https://man7.org/linux/man-pages/man2/tee.2.html), and
+/// it's only available on Linux. It blocks until `reader` has some data and
+/// `writer` has some buffer space, and zero means that all of `reader`'s
+/// writers are closed and it's empty. Since the data stays in `reader`, the
+/// usual pattern is to `tee` into one pipe and then [`splice`](fn.splice.html)
+/// the same number of bytes into another.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let (source, mut source_writer) = os_pipe::pipe()?;
+/// let (mut log, log_writer) = os_pipe::pipe()?;
+/// let (mut consumer, consumer_writer) = os_pipe::pipe()?;
+/// source_writer.write_all(b"hello")?;
+/// drop(source_writer);
+/// loop {
+///     let n = os_pipe::tee(&source, &log_writer, 1 << 16)?;
+///     if n == 0 {
+///         break;
+///     }
+///     let mut spliced = 0;
+///     while spliced < n {
+///         spliced += source.splice_to(&consumer_writer, n - spliced)?;
+///     }
+/// }
+/// drop(log_writer);
+/// drop(consumer_writer);
+/// let mut output = String::new();
+/// log.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// output.clear();
+/// consumer.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn tee(reader: &PipeReader, writer: &PipeWriter, len: usize) -> io::Result<usize> {
+    let res = unsafe { libc::tee(reader.as_raw_fd(), writer.as_raw_fd(), len, 0) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+// vmsplice(2) maps the caller's pages into the pipe, so the data isn't copied until it's read out
+// the other side. That's why the safe methods below only accept memory that can never change.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sys_vmsplice(fd: BorrowedFd, buf: &[u8], flags: c_uint) -> io::Result<usize> {
+    let iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let res = unsafe { libc::vmsplice(fd.as_raw_fd(), &iov, 1, flags) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl PipeWriter {
+    /// Map up to `buf.len()` bytes of `buf` into this pipe without copying
+    /// them, and return the number of bytes mapped. This is
+    /// [`vmsplice(2)`](https://man7.org/linux/man-pages/man2/vmsplice.2.html),
+    /// and it's only available on Linux.
+    ///
+    /// The pipe refers to the memory of `buf` until the data is read out the
+    /// other side, so `buf` must never change or be freed. Requiring `'static`
+    /// shared memory guarantees that. For memory that you manage yourself, see
+    /// [`vmsplice_unchecked`](#method.vmsplice_unchecked).
+    pub fn vmsplice(&self, buf: &'static [u8]) -> io::Result<usize> {
+        sys_vmsplice(self.as_fd(), buf, 0)
+    }
+
+    /// Give all of `buf` to the pipe with `SPLICE_F_GIFT`, blocking until it's
+    /// all been mapped in. This is only available on Linux.
+    ///
+    /// A gift is permanent: the kernel requires that the application never
+    /// modify gifted memory again, which `&'static [u8]` guarantees. To gift
+    /// an owned buffer, you have to leak it with
+    /// [`Box::leak`](https://doc.rust-lang.org/std/boxed/struct.Box.html#method.leak).
+    ///
+    /// Gifting used to let a reader that splices out of the pipe with
+    /// [`SpliceFlags::MOVE`](struct.SpliceFlags.html#associatedconstant.MOVE)
+    /// take the pages without copying them, but `MOVE` has been a no-op since
+    /// Linux 2.6.21, so on modern kernels this is no faster than
+    /// [`vmsplice`](#method.vmsplice).
+    pub fn vmsplice_gift(&self, mut buf: &'static [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match sys_vmsplice(self.as_fd(), buf, libc::SPLICE_F_GIFT) {
+                Ok(n) => buf = &buf[n..],
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`vmsplice`](#method.vmsplice), but for memory with any lifetime.
+    /// This is only available on Linux.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not modify or free the first `n` bytes of `buf`, where
+    /// `n` is the return value, until they've been read out of the pipe.
+    /// Otherwise the reader could see the modified bytes, or whatever the
+    /// allocator reuses that memory for.
+    pub unsafe fn vmsplice_unchecked(&self, buf: &[u8]) -> io::Result<usize> {
+        sys_vmsplice(self.as_fd(), buf, 0)
+    }
+}
+
+impl PipeReader {
+    /// Move up to `len` bytes out of this pipe and into `to`, without copying
+    /// them through userspace. This is [`splice`](fn.splice.html) with no
+    /// flags. This is only available on Unix.
+    pub fn splice_to(&self, to: impl AsFd, len: usize) -> io::Result<usize> {
+        splice(self, to, len, SpliceFlags::NONE)
+    }
+}
+
+impl PipeWriter {
+    /// Move up to `len` bytes out of `from` and into this pipe, without
+    /// copying them through userspace. This is [`splice`](fn.splice.html) with
+    /// no flags. This is only available on Unix.
+    pub fn splice_from(&self, from: impl AsFd, len: usize) -> io::Result<usize> {
+        splice(from, self, len, SpliceFlags::NONE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::prelude::*;
+    use std::io::SeekFrom;
+
+    #[test]
+    fn test_splice_pipe_to_pipe() {
+        let (reader1, mut writer1) = crate::pipe().unwrap();
+        let (mut reader2, writer2) = crate::pipe().unwrap();
+        writer1.write_all(b"some stuff").unwrap();
+        drop(writer1);
+        let mut total = 0;
+        loop {
+            let n = reader1.splice_to(&writer2, 100).unwrap();
+            if n == 0 {
+                break;
+            }
+            total += n;
+        }
+        assert_eq!(total, 10);
+        drop(writer2);
+        let mut out = String::new();
+        reader2.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "some stuff");
+    }
+
+    #[test]
+    fn test_splice_file_to_pipe() {
+        let path = std::env::temp_dir().join(format!("os_pipe_test_splice_{}", std::process::id()));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file.write_all(b"some stuff").unwrap();
+        file.seek(SeekFrom::Start(5)).unwrap();
+
+        let (mut reader, writer) = crate::pipe().unwrap();
+        let n = writer.splice_from(&file, 100).unwrap();
+        assert_eq!(n, 5);
+        // The file offset should've advanced.
+        assert_eq!(writer.splice_from(&file, 100).unwrap(), 0);
+        drop(writer);
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "stuff");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_tee_doesnt_consume() {
+        let (mut reader1, mut writer1) = crate::pipe().unwrap();
+        let (mut reader2, writer2) = crate::pipe().unwrap();
+        writer1.write_all(b"some stuff").unwrap();
+        drop(writer1);
+        assert_eq!(tee(&reader1, &writer2, 4).unwrap(), 4);
+        drop(writer2);
+        let mut out = String::new();
+        reader2.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "some");
+        // The original pipe still has everything.
+        out.clear();
+        reader1.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "some stuff");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_vmsplice() {
+        let (mut reader, writer) = crate::pipe().unwrap();
+        assert_eq!(writer.vmsplice(b"some ").unwrap(), 5);
+        let gift: &'static [u8] = Box::leak(vec![b'x'; 100_000].into_boxed_slice());
+        // The gift is bigger than the pipe buffer, so we need another thread to read it.
+        let joiner = std::thread::spawn(move || writer.vmsplice_gift(gift));
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        joiner.join().unwrap().unwrap();
+        assert_eq!(&out[..5], b"some ");
+        assert_eq!(out.len(), 100_005);
+        assert!(out[5..].iter().all(|&b| b == b'x'));
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn test_splice_fallback_nonblocking() {
+        let (reader1, mut writer1) = crate::pipe().unwrap();
+        let (mut reader2, writer2) = crate::pipe().unwrap();
+        writer1.write_all(b"foo").unwrap();
+        let err = splice(&reader1, &writer2, 100, SpliceFlags::NONBLOCK).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        writer2.set_nonblocking(true).unwrap();
+        let err = reader1.splice_to(&writer2, 100).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        // Nothing was read, so nothing was lost.
+        writer2.set_nonblocking(false).unwrap();
+        assert_eq!(reader1.splice_to(&writer2, 100).unwrap(), 3);
+        drop(writer2);
+        let mut out = String::new();
+        reader2.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "foo");
+    }
+
+    #[test]
+    fn test_splice_flags() {
+        let mut flags = SpliceFlags::MOVE | SpliceFlags::MORE;
+        assert!(flags.contains(SpliceFlags::MOVE));
+        assert!(!flags.contains(SpliceFlags::NONBLOCK));
+        flags |= SpliceFlags::NONBLOCK;
+        assert!(flags.contains(SpliceFlags::MORE | SpliceFlags::NONBLOCK));
+        assert!(flags.contains(SpliceFlags::NONE));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::PipeWriter;
+use std::io;
+use std::io::prelude::*;
+
+#[derive(Clone, Copy, Debug)]
+enum StdStream {
+    Stdout,
+    Stderr,
+}
+
+/// A duplicated copy of the process's stdout or stderr that stays in sync
+/// with Rust's own handle, returned by
+/// [`dup_stdout_synced`](fn.dup_stdout_synced.html) and
+/// [`dup_stderr_synced`](fn.dup_stderr_synced.html).
+///
+/// [`std::io::stdout`] is line-buffered, so output from `print!` can sit in
+/// its buffer for a while. Bytes written directly to a
+/// [`dup_stdout`](fn.dup_stdout.html) copy can jump ahead of it. Each write to
+/// a `SyncedWriter` locks the standard library's handle, flushes it, and then
+/// writes to the pipe while still holding the lock, so output from both comes
+/// out in the order it was written, and `println!` from other threads can't
+/// land in the middle of a `write_all`. Stderr isn't buffered, but the lock
+/// still keeps writes from interleaving.
+///
+/// To pass the stream to a child process, use
+/// [`into_inner`](#method.into_inner). The child's writes aren't synchronized.
+///
+/// [`std::io::stdout`]: https://doc.rust-lang.org/std/io/fn.stdout.html
+#[derive(Debug)]
+pub struct SyncedWriter {
+    writer: PipeWriter,
+    stream: StdStream,
+}
+
+impl SyncedWriter {
+    /// Return the underlying pipe, without synchronization.
+    pub fn into_inner(self) -> PipeWriter {
+        self.writer
+    }
+
+    // Lock and flush the standard library's handle, then call `f` with the lock held.
+    fn with_lock<T>(&mut self, f: impl FnOnce(&mut PipeWriter) -> io::Result<T>) -> io::Result<T> {
+        match self.stream {
+            StdStream::Stdout => {
+                let mut lock = io::stdout().lock();
+                lock.flush()?;
+                f(&mut self.writer)
+            }
+            StdStream::Stderr => {
+                let mut lock = io::stderr().lock();
+                lock.flush()?;
+                f(&mut self.writer)
+            }
+        }
+    }
+}
+
+impl Write for SyncedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_lock(|writer| writer.write(buf))
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.with_lock(|writer| writer.write_vectored(bufs))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        // Hold the lock for the whole buffer, rather than taking it once per write.
+        self.with_lock(|writer| writer.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with_lock(|writer| writer.flush())
+    }
+}
+
+/// Like [`dup_stdout`](fn.dup_stdout.html), but each write is synchronized
+/// with [`std::io::stdout`]. See [`SyncedWriter`](struct.SyncedWriter.html).
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let mut stdout = os_pipe::dup_stdout_synced()?;
+/// // This sits in the standard library's buffer, because it has no newline...
+/// print!("hello ");
+/// // ...but it's flushed before this write, so it comes out first.
+/// stdout.write_all(b"world\n")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`std::io::stdout`]: https://doc.rust-lang.org/std/io/fn.stdout.html
+pub fn dup_stdout_synced() -> io::Result<SyncedWriter> {
+    Ok(SyncedWriter {
+        writer: crate::dup_stdout()?,
+        stream: StdStream::Stdout,
+    })
+}
+
+/// Like [`dup_stderr`](fn.dup_stderr.html), but each write is synchronized
+/// with [`std::io::stderr`]. See [`SyncedWriter`](struct.SyncedWriter.html).
+///
+/// [`std::io::stderr`]: https://doc.rust-lang.org/std/io/fn.stderr.html
+pub fn dup_stderr_synced() -> io::Result<SyncedWriter> {
+    Ok(SyncedWriter {
+        writer: crate::dup_stderr()?,
+        stream: StdStream::Stderr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::path_to_exe;
+    use std::process::Command;
+
+    #[test]
+    fn test_synced_stdout() {
+        // The synced binary mixes print! with writes to a synced dup. The test harness doesn't send
+        // print! to the real stdout, so this has to run in a separate process.
+        let output = Command::new(path_to_exe("synced")).output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"print! synced print!\n");
+    }
+}
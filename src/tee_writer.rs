@@ -0,0 +1,199 @@
+use crate::PipeWriter;
+use std::io;
+use std::io::prelude::*;
+
+/// What a [`TeeWriter`](struct.TeeWriter.html) should do when one of its
+/// sinks fails with [`BrokenPipe`], meaning that all of its readers are
+/// closed.
+///
+/// [`BrokenPipe`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.BrokenPipe
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BrokenPipePolicy {
+    /// Return the error from the write. This is the default.
+    #[default]
+    Fail,
+    /// Drop that sink, and keep writing to the others.
+    DropSink,
+}
+
+/// A writer that writes everything to several sinks, like the `tee` command.
+///
+/// This is useful for sending one stream of output to several readers, like a
+/// logger and a parser. Each call to `write` writes the entire buffer to every
+/// sink in order. If a sink fails, it may have received part of the buffer,
+/// and `write` still writes all of it to the others. It only returns the error
+/// if no sink received the whole buffer. Otherwise the buffer can't be taken
+/// back, so `write` reports success and the error is returned by the next call
+/// to `write` or `flush`. Writing to a `TeeWriter` with no sinks left returns
+/// an error of kind [`BrokenPipe`].
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::prelude::*;
+///
+/// let (mut log_reader, log_writer) = os_pipe::pipe()?;
+/// let (mut parse_reader, parse_writer) = os_pipe::pipe()?;
+/// let mut tee = os_pipe::TeeWriter::new([log_writer, parse_writer]);
+/// tee.write_all(b"hello")?;
+/// drop(tee);
+///
+/// let mut output = String::new();
+/// log_reader.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// output.clear();
+/// parse_reader.read_to_string(&mut output)?;
+/// assert_eq!(output, "hello");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`BrokenPipe`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.BrokenPipe
+#[derive(Debug)]
+pub struct TeeWriter<W = PipeWriter> {
+    sinks: Vec<W>,
+    broken_pipe_policy: BrokenPipePolicy,
+    // An error from a write that some sinks accepted, to report next time.
+    pending_error: Option<io::Error>,
+}
+
+impl<W: Write> TeeWriter<W> {
+    /// Create a `TeeWriter` that writes to all of `sinks`, with
+    /// [`BrokenPipePolicy::Fail`](enum.BrokenPipePolicy.html#variant.Fail).
+    pub fn new(sinks: impl IntoIterator<Item = W>) -> TeeWriter<W> {
+        TeeWriter {
+            sinks: sinks.into_iter().collect(),
+            broken_pipe_policy: BrokenPipePolicy::default(),
+            pending_error: None,
+        }
+    }
+
+    /// Set what happens when a sink fails with `BrokenPipe`.
+    pub fn set_broken_pipe_policy(&mut self, policy: BrokenPipePolicy) {
+        self.broken_pipe_policy = policy;
+    }
+
+    /// Add another sink.
+    pub fn push(&mut self, sink: W) {
+        self.sinks.push(sink);
+    }
+
+    /// The sinks that haven't been dropped.
+    pub fn sinks(&self) -> &[W] {
+        &self.sinks
+    }
+
+    /// Return the sinks that haven't been dropped.
+    pub fn into_inner(self) -> Vec<W> {
+        self.sinks
+    }
+
+    // Apply `f` to every sink, dropping the ones that fail with BrokenPipe if the policy says to.
+    // Return the number of sinks that succeeded, and the first error.
+    fn for_each_sink(
+        &mut self,
+        mut f: impl FnMut(&mut W) -> io::Result<()>,
+    ) -> (usize, Option<io::Error>) {
+        let mut succeeded = 0;
+        let mut error = None;
+        let mut i = 0;
+        while i < self.sinks.len() {
+            match f(&mut self.sinks[i]) {
+                Ok(()) => {
+                    succeeded += 1;
+                    i += 1;
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::BrokenPipe
+                        && self.broken_pipe_policy == BrokenPipePolicy::DropSink =>
+                {
+                    self.sinks.remove(i);
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                    i += 1;
+                }
+            }
+        }
+        if self.sinks.is_empty() && error.is_none() {
+            error = Some(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "TeeWriter has no sinks left",
+            ));
+        }
+        (succeeded, error)
+    }
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        match self.for_each_sink(|sink| sink.write_all(buf)) {
+            (0, Some(e)) => Err(e),
+            (_, Some(e)) => {
+                self.pending_error = Some(e);
+                Ok(buf.len())
+            }
+            (_, None) => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        match self.for_each_sink(|sink| sink.flush()) {
+            (_, Some(e)) => Err(e),
+            (_, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broken_pipe_policies() {
+        let (mut reader1, writer1) = crate::pipe().unwrap();
+        let (reader2, writer2) = crate::pipe().unwrap();
+        let mut tee = TeeWriter::new([writer1, writer2]);
+        drop(reader2);
+
+        // By default, a closed reader fails the write. The first sink got the bytes, so the write
+        // succeeds, and the error comes from the next call.
+        assert_eq!(tee.write(b"foo").unwrap(), 3);
+        let err = tee.flush().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(tee.sinks().len(), 2);
+        tee.flush().unwrap();
+
+        // With DropSink, the closed reader's sink goes away.
+        tee.set_broken_pipe_policy(BrokenPipePolicy::DropSink);
+        tee.write_all(b"bar").unwrap();
+        assert_eq!(tee.sinks().len(), 1);
+
+        // Once every sink is gone, writes fail.
+        let mut out = [0; 6];
+        reader1.read_exact(&mut out).unwrap();
+        assert_eq!(&out, b"foobar");
+        drop(reader1);
+        let err = tee.write(b"baz").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert!(tee.sinks().is_empty());
+    }
+
+    #[test]
+    fn test_no_sink_accepts_write() {
+        let (reader, writer) = crate::pipe().unwrap();
+        let mut tee = TeeWriter::new([writer]);
+        drop(reader);
+        // No sink got the bytes, so the write itself fails, and there's nothing left to report.
+        let err = tee.write(b"foo").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        tee.flush().unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Async pipe types for the [Tokio](https://tokio.rs) runtime, available on
+//! Unix with the `tokio` Cargo feature.
+//!
+//! These wrap a [`PipeReader`] or [`PipeWriter`], put it in non-blocking mode,
+//! and register it with the runtime's reactor using
+//! [`AsyncFd`](https://docs.rs/tokio/latest/tokio/io/unix/struct.AsyncFd.html).
+//! Since non-blocking mode is shared with every copy of a pipe end, don't give
+//! clones of a wrapped pipe end to child processes or blocking code.
+//!
+//! # Example
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use os_pipe::tokio::{AsyncPipeReader, AsyncPipeWriter};
+//! use std::future::poll_fn;
+//! use std::pin::Pin;
+//! use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread()
+//!     .enable_io()
+//!     .build()?;
+//! runtime.block_on(async {
+//!     let (reader, writer) = os_pipe::pipe()?;
+//!     let mut reader = AsyncPipeReader::new(reader)?;
+//!     let mut writer = AsyncPipeWriter::new(writer)?;
+//!     // With Tokio's io-util feature, AsyncWriteExt and AsyncReadExt make this shorter.
+//!     let n = poll_fn(|cx| Pin::new(&mut writer).poll_write(cx, b"hello")).await?;
+//!     assert_eq!(n, 5);
+//!     let mut buf = [0; 5];
+//!     let mut buf = ReadBuf::new(&mut buf);
+//!     poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf)).await?;
+//!     assert_eq!(buf.filled(), b"hello");
+//!     Ok(())
+//! })
+//! # }
+//! ```
+//!
+//! [`PipeReader`]: ../struct.PipeReader.html
+//! [`PipeWriter`]: ../struct.PipeWriter.html
+
+use crate::{PipeReader, PipeWriter};
+use ::tokio::io::unix::AsyncFd;
+use ::tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use std::io::{self, Read, Write};
+use std::os::unix::prelude::*;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The reading end of a pipe, registered with the Tokio runtime.
+///
+/// This implements Tokio's `AsyncRead`.
+#[derive(Debug)]
+pub struct AsyncPipeReader(AsyncFd<PipeReader>);
+
+impl AsyncPipeReader {
+    /// Put `reader` in non-blocking mode and register it with the current
+    /// Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// This panics if it's called outside of a Tokio runtime, or if the
+    /// runtime doesn't have IO enabled.
+    pub fn new(reader: PipeReader) -> io::Result<AsyncPipeReader> {
+        reader.set_nonblocking(true)?;
+        // SAFETY: PipeReader owns its fd, and it never changes.
+        let async_fd = unsafe { AsyncFd::register(reader)? };
+        Ok(AsyncPipeReader(async_fd))
+    }
+
+    /// Get a reference to the underlying [`PipeReader`](../struct.PipeReader.html).
+    pub fn get_ref(&self) -> &PipeReader {
+        self.0.get_ref()
+    }
+
+    /// Deregister the reader from the runtime, put it back in blocking mode,
+    /// and return it.
+    pub fn into_inner(self) -> io::Result<PipeReader> {
+        let reader = self.0.into_inner();
+        reader.set_nonblocking(false)?;
+        Ok(reader)
+    }
+}
+
+impl TryFrom<PipeReader> for AsyncPipeReader {
+    type Error = io::Error;
+
+    fn try_from(reader: PipeReader) -> io::Result<AsyncPipeReader> {
+        AsyncPipeReader::new(reader)
+    }
+}
+
+impl AsyncRead for AsyncPipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = match self.0.poll_read_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            };
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| inner.get_ref().read(unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                // The readiness was stale, and try_io has cleared it. Poll again.
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsFd for AsyncPipeReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for AsyncPipeReader {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The writing end of a pipe, registered with the Tokio runtime.
+///
+/// This implements Tokio's `AsyncWrite`. Flushing does nothing, and
+/// shutting down doesn't close the pipe. Drop the writer to close it.
+#[derive(Debug)]
+pub struct AsyncPipeWriter(AsyncFd<PipeWriter>);
+
+impl AsyncPipeWriter {
+    /// Put `writer` in non-blocking mode and register it with the current
+    /// Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// This panics if it's called outside of a Tokio runtime, or if the
+    /// runtime doesn't have IO enabled.
+    pub fn new(writer: PipeWriter) -> io::Result<AsyncPipeWriter> {
+        writer.set_nonblocking(true)?;
+        // SAFETY: PipeWriter owns its fd, and it never changes.
+        let async_fd = unsafe { AsyncFd::register(writer)? };
+        Ok(AsyncPipeWriter(async_fd))
+    }
+
+    /// Get a reference to the underlying [`PipeWriter`](../struct.PipeWriter.html).
+    pub fn get_ref(&self) -> &PipeWriter {
+        self.0.get_ref()
+    }
+
+    /// Deregister the writer from the runtime, put it back in blocking mode,
+    /// and return it.
+    pub fn into_inner(self) -> io::Result<PipeWriter> {
+        let writer = self.0.into_inner();
+        writer.set_nonblocking(false)?;
+        Ok(writer)
+    }
+}
+
+impl TryFrom<PipeWriter> for AsyncPipeWriter {
+    type Error = io::Error;
+
+    fn try_from(writer: PipeWriter) -> io::Result<AsyncPipeWriter> {
+        AsyncPipeWriter::new(writer)
+    }
+}
+
+impl AsyncWrite for AsyncPipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = match self.0.poll_write_ready(cx) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            };
+            match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                Ok(result) => return Poll::Ready(result),
+                // The readiness was stale, and try_io has cleared it. Poll again.
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsFd for AsyncPipeWriter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for AsyncPipeWriter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{poll_fn, Future};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        ::tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    // Tokio's io-util feature has these, but we don't want to turn it on for every caller.
+    async fn write_all(writer: &mut AsyncPipeWriter, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await?;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    async fn read_to_end(reader: &mut AsyncPipeReader, out: &mut Vec<u8>) -> io::Result<()> {
+        let mut buf = [0; 1 << 16];
+        loop {
+            let mut read_buf = ReadBuf::new(&mut buf);
+            poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut read_buf)).await?;
+            if read_buf.filled().is_empty() {
+                return Ok(());
+            }
+            out.extend_from_slice(read_buf.filled());
+        }
+    }
+
+    #[test]
+    fn test_async_pipe_a_megabyte_of_data() {
+        // Unlike the blocking version of this test, we don't need a second thread. The runtime
+        // interleaves the writer task with the reads when the pipe buffer fills up.
+        let data = vec![0xff; 1_000_000];
+        let out = block_on(async {
+            let (reader, writer) = crate::pipe().unwrap();
+            let mut reader = AsyncPipeReader::new(reader).unwrap();
+            let mut writer = AsyncPipeWriter::new(writer).unwrap();
+            let write_data = data.clone();
+            let write_task = ::tokio::spawn(async move {
+                write_all(&mut writer, &write_data).await.unwrap();
+            });
+            let mut out = Vec::new();
+            read_to_end(&mut reader, &mut out).await.unwrap();
+            write_task.await.unwrap();
+            out
+        });
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_into_inner_restores_blocking() {
+        fn is_nonblocking(fd: &impl AsRawFd) -> bool {
+            let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+            assert!(flags != -1);
+            flags & libc::O_NONBLOCK != 0
+        }
+
+        block_on(async {
+            let (reader, _writer) = crate::pipe().unwrap();
+            let reader = AsyncPipeReader::new(reader).unwrap();
+            assert!(is_nonblocking(&reader));
+            let reader = reader.into_inner().unwrap();
+            assert!(!is_nonblocking(&reader));
+        });
+    }
+}
@@ -1,10 +1,19 @@
+use crate::PipeBuilder;
 use crate::PipeReader;
 use crate::PipeWriter;
-use libc::c_int;
 use std::fs::File;
 use std::io;
+use std::os::raw::c_int;
 use std::os::unix::prelude::*;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const PIPE_BUF: usize = libc::PIPE_BUF;
+
+// Not every platform defines PIPE_BUF in libc, so elsewhere we use the POSIX minimum,
+// _POSIX_PIPE_BUF.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) const PIPE_BUF: usize = 512;
+
 // We need to atomically create pipes and set the CLOEXEC flag on them. This is
 // done with the pipe2() API. However, macOS doesn't support pipe2. There, all
 // we can do is call pipe() followed by fcntl(), and hope that no other threads
@@ -17,9 +26,9 @@ use std::os::unix::prelude::*;
     target_os = "macos",
     target_os = "haiku"
 )))]
-fn pipe2_cloexec() -> io::Result<(OwnedFd, OwnedFd)> {
+fn pipe2(flags: c_int) -> io::Result<(OwnedFd, OwnedFd)> {
     let mut fds: [c_int; 2] = [0; 2];
-    let res = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+    let res = unsafe { libc::pipe2(fds.as_mut_ptr(), flags) };
     if res != 0 {
         return Err(io::Error::last_os_error());
     }
@@ -33,7 +42,7 @@ fn pipe2_cloexec() -> io::Result<(OwnedFd, OwnedFd)> {
     target_os = "macos",
     target_os = "haiku"
 ))]
-fn pipe2_cloexec() -> io::Result<(OwnedFd, OwnedFd)> {
+fn pipe2(flags: c_int) -> io::Result<(OwnedFd, OwnedFd)> {
     let mut fds: [c_int; 2] = [0; 2];
     let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
     if res != 0 {
@@ -42,19 +51,59 @@ fn pipe2_cloexec() -> io::Result<(OwnedFd, OwnedFd)> {
     // Wrap the fds immediately, so that we'll drop them and close them in the unlikely event that
     // any of the following fcntls fails.
     let owned_fds = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
-    let res = unsafe { libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC) };
-    if res != 0 {
-        return Err(io::Error::last_os_error());
+    if flags & libc::O_CLOEXEC != 0 {
+        let res = unsafe { libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let res = unsafe { libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
     }
-    let res = unsafe { libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC) };
-    if res != 0 {
-        return Err(io::Error::last_os_error());
+    if flags & libc::O_NONBLOCK != 0 {
+        set_nonblocking(&owned_fds.0, true)?;
+        set_nonblocking(&owned_fds.1, true)?;
     }
     Ok(owned_fds)
 }
 
-pub(crate) fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
-    let (read_fd, write_fd) = pipe2_cloexec()?;
+// O_DIRECT turns a pipe into a "packet mode" pipe, where each write is a separate packet and each
+// read returns at most one packet. This is Linux-specific.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn direct_flag() -> io::Result<c_int> {
+    Ok(libc::O_DIRECT)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn direct_flag() -> io::Result<c_int> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "packet mode pipes are only supported on Linux",
+    ))
+}
+
+pub(crate) fn pipe(builder: &PipeBuilder) -> io::Result<(PipeReader, PipeWriter)> {
+    let mut flags = 0;
+    if !builder.inheritable {
+        flags |= libc::O_CLOEXEC;
+    }
+    if builder.direct {
+        flags |= direct_flag()?;
+    }
+    // pipe2() sets O_NONBLOCK on both ends or neither. If only one end should be non-blocking, we
+    // fix that up with fcntl() below.
+    if builder.reader_nonblocking && builder.writer_nonblocking {
+        flags |= libc::O_NONBLOCK;
+    }
+    let (read_fd, write_fd) = pipe2(flags)?;
+    if builder.reader_nonblocking != builder.writer_nonblocking {
+        set_nonblocking(&read_fd, builder.reader_nonblocking)?;
+        set_nonblocking(&write_fd, builder.writer_nonblocking)?;
+    }
+    if let Some(capacity) = builder.capacity {
+        set_capacity(&write_fd, capacity)?;
+    }
     Ok((read_fd.into(), write_fd.into()))
 }
 
@@ -62,6 +111,151 @@ pub(crate) fn dup(handle: impl AsFd) -> io::Result<OwnedFd> {
     handle.as_fd().try_clone_to_owned()
 }
 
+pub(crate) fn set_nonblocking(fd: impl AsFd, nonblocking: bool) -> io::Result<()> {
+    let fd = fd.as_fd().as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let new_flags = if nonblocking {
+        flags | libc::O_NONBLOCK
+    } else {
+        flags & !libc::O_NONBLOCK
+    };
+    if new_flags != flags {
+        let res = unsafe { libc::fcntl(fd, libc::F_SETFL, new_flags) };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn capacity(fd: impl AsFd) -> io::Result<usize> {
+    let res = unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), libc::F_GETPIPE_SZ) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_capacity(fd: impl AsFd, capacity: usize) -> io::Result<usize> {
+    let capacity: c_int = capacity
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "pipe capacity is too large"))?;
+    let res = unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), libc::F_SETPIPE_SZ, capacity) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn capacity_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "resizing pipes is only supported on Linux",
+    )
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn capacity(_fd: impl AsFd) -> io::Result<usize> {
+    Err(capacity_unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn set_capacity(_fd: impl AsFd, _capacity: usize) -> io::Result<usize> {
+    Err(capacity_unsupported())
+}
+
+pub(crate) fn is_inheritable(fd: impl AsFd) -> io::Result<bool> {
+    let flags = unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), libc::F_GETFD) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags & libc::FD_CLOEXEC == 0)
+}
+
+pub(crate) fn set_inheritable(fd: impl AsFd, inheritable: bool) -> io::Result<()> {
+    let fd = fd.as_fd().as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let new_flags = if inheritable {
+        flags & !libc::FD_CLOEXEC
+    } else {
+        flags | libc::FD_CLOEXEC
+    };
+    if new_flags != flags {
+        let res = unsafe { libc::fcntl(fd, libc::F_SETFD, new_flags) };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Safety: the caller must not close an fd that something else owns.
+pub(crate) unsafe fn dup_to(fd: impl AsFd, target: RawFd, inheritable: bool) -> io::Result<()> {
+    let fd = fd.as_fd();
+    if fd.as_raw_fd() == target {
+        // dup2 would do nothing here, and dup3 would fail with EINVAL.
+        return set_inheritable(fd, inheritable);
+    }
+    loop {
+        let res = unsafe { dup_to_inner(fd.as_raw_fd(), target, inheritable) };
+        if res != -1 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+))]
+unsafe fn dup_to_inner(fd: RawFd, target: RawFd, inheritable: bool) -> c_int {
+    let flags = if inheritable { 0 } else { libc::O_CLOEXEC };
+    unsafe { libc::dup3(fd, target, flags) }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly",
+)))]
+unsafe fn dup_to_inner(fd: RawFd, target: RawFd, inheritable: bool) -> c_int {
+    // Without dup3 there's a window where the target is inheritable even if it shouldn't be.
+    let res = unsafe { libc::dup2(fd, target) };
+    if res == -1 || inheritable {
+        return res;
+    }
+    unsafe { libc::fcntl(target, libc::F_SETFD, libc::FD_CLOEXEC) }
+}
+
+pub(crate) fn bytes_available(fd: impl AsFd) -> io::Result<usize> {
+    let mut available: c_int = 0;
+    let res = unsafe { libc::ioctl(fd.as_fd().as_raw_fd(), libc::FIONREAD, &mut available) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available as usize)
+}
+
 impl IntoRawFd for PipeReader {
     fn into_raw_fd(self) -> RawFd {
         self.0.into_raw_fd()
@@ -0,0 +1,144 @@
+use crate::{PipeReader, PipeWriter};
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::prelude::*;
+
+/// A self-pipe for waking up a thread that's blocked in `poll`, `select`, or
+/// `epoll_wait`.
+///
+/// The poll loop waits for the waker's file descriptor (see [`AsFd`]) to
+/// become readable, alongside whatever else it's waiting on. Other threads call
+/// [`wake`](#method.wake) to make it readable, and the poll loop calls
+/// [`drain`](#method.drain) before it goes back to sleep. Both
+/// ends of the pipe are non-blocking, so waking never blocks: if the pipe's
+/// buffer is already full, there's already a wakeup pending, and the extra
+/// wakeup is coalesced into it. Share a `Waker` between threads by putting it
+/// in an `Arc`.
+///
+/// This type is only available on Unix.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::os::unix::io::AsRawFd;
+/// use std::sync::Arc;
+///
+/// let waker = Arc::new(os_pipe::Waker::new()?);
+/// let waker_clone = waker.clone();
+/// std::thread::spawn(move || waker_clone.wake().unwrap());
+///
+/// // Block until the other thread wakes us up.
+/// let mut pollfd = libc::pollfd {
+///     fd: waker.as_raw_fd(),
+///     events: libc::POLLIN,
+///     revents: 0,
+/// };
+/// let ret = unsafe { libc::poll(&mut pollfd, 1, -1) };
+/// assert_eq!(ret, 1);
+/// waker.drain()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`AsFd`]: https://doc.rust-lang.org/std/os/fd/trait.AsFd.html
+#[derive(Debug)]
+pub struct Waker {
+    reader: PipeReader,
+    writer: PipeWriter,
+}
+
+impl Waker {
+    /// Open a new non-blocking pipe for the waker.
+    pub fn new() -> io::Result<Waker> {
+        let (reader, writer) = crate::pipe_with(true)?;
+        Ok(Waker { reader, writer })
+    }
+
+    /// Make the waker's file descriptor readable, if it isn't already.
+    ///
+    /// This only calls `write`, which is async-signal-safe, but a failed
+    /// `write` changes `errno`. A signal handler that calls this has to save
+    /// and restore `errno` itself.
+    pub fn wake(&self) -> io::Result<()> {
+        loop {
+            match (&self.writer).write(&[1]) {
+                Ok(_) => return Ok(()),
+                // The pipe is full, so the reader already has a wakeup pending.
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Consume all pending wakeups, so that the waker's file descriptor is no
+    /// longer readable. This never blocks.
+    pub fn drain(&self) -> io::Result<()> {
+        let mut buf = [0; 1024];
+        loop {
+            match (&self.reader).read(&mut buf) {
+                // We hold the writer, so EOF should be impossible, but it would mean there's
+                // nothing left to read either way.
+                Ok(0) => return Ok(()),
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsFd for Waker {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.reader.as_fd()
+    }
+}
+
+impl AsRawFd for Waker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_readable(waker: &Waker) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: waker.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut pollfd, 1, 0) };
+        assert!(ret != -1);
+        ret == 1
+    }
+
+    #[test]
+    fn test_wake_and_drain() {
+        let waker = Waker::new().unwrap();
+        assert!(!is_readable(&waker));
+        waker.wake().unwrap();
+        waker.wake().unwrap();
+        assert!(is_readable(&waker));
+        waker.drain().unwrap();
+        assert!(!is_readable(&waker));
+        // Draining an empty waker is fine too.
+        waker.drain().unwrap();
+    }
+
+    #[test]
+    fn test_wakeups_coalesce_when_full() {
+        let waker = Waker::new().unwrap();
+        // This is more than enough to fill a default pipe buffer on any platform. None of these
+        // calls should block or fail.
+        for _ in 0..200_000 {
+            waker.wake().unwrap();
+        }
+        waker.drain().unwrap();
+        assert!(!is_readable(&waker));
+    }
+}
@@ -1,20 +1,48 @@
+use crate::PipeBuilder;
 use crate::PipeReader;
 use crate::PipeWriter;
 use std::fs::File;
 use std::io;
+use std::mem;
 use std::os::windows::prelude::*;
 use std::ptr;
-use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
-use windows_sys::Win32::System::Pipes::CreatePipe;
+use windows_sys::Win32::Foundation::{
+    GetHandleInformation, SetHandleInformation, ERROR_BROKEN_PIPE, HANDLE_FLAG_INHERIT,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Security::SECURITY_ATTRIBUTES;
+use windows_sys::Win32::System::Pipes::{CreatePipe, PeekNamedPipe};
+
+pub(crate) fn pipe(builder: &PipeBuilder) -> io::Result<(PipeReader, PipeWriter)> {
+    if builder.reader_nonblocking || builder.writer_nonblocking {
+        return Err(nonblocking_unsupported());
+    }
+    if builder.direct {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "packet mode pipes are only supported on Linux",
+        ));
+    }
+    // CreatePipe treats a size of zero as "use the default".
+    let size = match builder.capacity {
+        Some(capacity) => capacity.try_into().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "pipe capacity is too large")
+        })?,
+        None => 0,
+    };
+    let attributes = SECURITY_ATTRIBUTES {
+        nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: ptr::null_mut(),
+        bInheritHandle: builder.inheritable as i32,
+    };
 
-pub(crate) fn pipe() -> io::Result<(PipeReader, PipeWriter)> {
     let mut read_pipe = INVALID_HANDLE_VALUE;
     let mut write_pipe = INVALID_HANDLE_VALUE;
 
     let ret = unsafe {
         // NOTE: These pipes do not support IOCP. We might want to emulate
         // anonymous pipes with CreateNamedPipe, as Rust's stdlib does.
-        CreatePipe(&mut read_pipe, &mut write_pipe, ptr::null_mut(), 0)
+        CreatePipe(&mut read_pipe, &mut write_pipe, &attributes, size)
     };
 
     if ret == 0 {
@@ -33,6 +61,89 @@ pub(crate) fn dup(handle: impl AsHandle) -> io::Result<OwnedHandle> {
     handle.as_handle().try_clone_to_owned()
 }
 
+// Anonymous pipes on Windows can be switched to PIPE_NOWAIT, but Microsoft documents that mode as
+// legacy, and ReadFile reports an empty pipe as ERROR_NO_DATA, which the standard library maps to
+// BrokenPipe. Rather than return misleading errors, we don't support non-blocking mode at all.
+fn nonblocking_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "non-blocking anonymous pipes are not supported on Windows",
+    )
+}
+
+pub(crate) fn set_nonblocking(_handle: impl AsHandle, nonblocking: bool) -> io::Result<()> {
+    if nonblocking {
+        Err(nonblocking_unsupported())
+    } else {
+        // Anonymous pipes are always blocking.
+        Ok(())
+    }
+}
+
+// CreatePipe takes a buffer size, but there's no way to query or change it afterwards.
+fn capacity_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "resizing pipes is only supported on Linux",
+    )
+}
+
+pub(crate) fn capacity(_handle: impl AsHandle) -> io::Result<usize> {
+    Err(capacity_unsupported())
+}
+
+pub(crate) fn set_capacity(_handle: impl AsHandle, _capacity: usize) -> io::Result<usize> {
+    Err(capacity_unsupported())
+}
+
+pub(crate) fn is_inheritable(handle: impl AsHandle) -> io::Result<bool> {
+    let mut flags = 0;
+    let ret = unsafe { GetHandleInformation(handle.as_handle().as_raw_handle() as _, &mut flags) };
+    if ret == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(flags & HANDLE_FLAG_INHERIT != 0)
+}
+
+pub(crate) fn set_inheritable(handle: impl AsHandle, inheritable: bool) -> io::Result<()> {
+    let flags = if inheritable { HANDLE_FLAG_INHERIT } else { 0 };
+    let ret = unsafe {
+        SetHandleInformation(
+            handle.as_handle().as_raw_handle() as _,
+            HANDLE_FLAG_INHERIT,
+            flags,
+        )
+    };
+    if ret == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn bytes_available(handle: impl AsHandle) -> io::Result<usize> {
+    let mut available = 0;
+    let ret = unsafe {
+        PeekNamedPipe(
+            handle.as_handle().as_raw_handle() as _,
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            &mut available,
+            ptr::null_mut(),
+        )
+    };
+    if ret == 0 {
+        let err = io::Error::last_os_error();
+        // Once all the writers are closed, Unix reports zero bytes available, and reads return
+        // EOF. Match that here.
+        if err.raw_os_error() == Some(ERROR_BROKEN_PIPE as i32) {
+            return Ok(0);
+        }
+        return Err(err);
+    }
+    Ok(available as usize)
+}
+
 impl IntoRawHandle for PipeReader {
     fn into_raw_handle(self) -> RawHandle {
         self.0.into_raw_handle()